@@ -0,0 +1,109 @@
+#![allow(clippy::needless_return)]
+
+// cargo bench, or cargo bench -- simulate_heat/boiling_pot to just run some of them.
+// every pass runs once per iteration on a fresh copy of the scene, so sleeping chunks don't make later samples cheaper
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+const SIZES: [usize; 3] = [64, 256, 512];
+const SCENES: [&str; 4] = ["sand_pile", "water_tank", "boiling_pot", "mostly_empty"];
+
+fn particle_type(id: u32, solid: bool, liquid_density: f32, gas_density: f32, melting_temperature: u16, boiling_temperature: u16) -> ParticleType {
+    return ParticleType {
+        vapor_color: [200, 200, 220, 80],
+        liquid_color: [40, 80, 220, 255],
+        solid_color: [120, 110, 90, 255],
+        solid,
+        liquid_density,
+        gas_density,
+        melting_temperature,
+        boiling_temperature,
+        heat_resistance: 4,
+        ..ParticleType::new(id)
+    }
+}
+
+fn scene(name: &str, size: usize) -> ParticleSim {
+    let air = particle_type(0, false, 0.001, 0.0012, 10, 20);
+    let water = particle_type(1, false, 1.0, 0.0006, 273, 373);
+    let stone = particle_type(2, true, 2.5, 2.5, 1500, 3000);
+    let sand = particle_type(3, false, 1.5, 1.5, 1500, 3000);
+
+    let mut sim = ParticleSim::new_seeded(size, size, Particle::new(air).set_temperature(300), 1);
+    let floor = size - size / 8;
+    for y in floor..size {
+        for x in 0..size {
+            sim.set_particle(x, y, Particle::new(stone).set_temperature(300));
+        }
+    }
+    match name {
+        "sand_pile" => {
+            sim.paint(size / 2, size / 3, size / 4, Particle::new(sand).set_temperature(300));
+        }
+        "water_tank" => {
+            for y in size / 3..floor {
+                for x in 0..size {
+                    let wall = x < size / 16 || x >= size - size / 16;
+                    sim.set_particle(x, y, Particle::new(if wall { stone } else { water }).set_temperature(300));
+                }
+            }
+        }
+        "boiling_pot" => {
+            for y in size / 2..floor {
+                for x in size / 4..size - size / 4 {
+                    sim.set_particle(x, y, Particle::new(water).set_temperature(360));
+                }
+            }
+            for y in floor..size {
+                for x in 0..size {
+                    sim.set_particle(x, y, Particle::new(stone).set_temperature(1200));
+                }
+            }
+        }
+        _ => {
+            for i in 0..8 {
+                sim.set_particle(size / 9 * (i + 1), size / 4, Particle::new(sand).set_temperature(300));
+            }
+        }
+    }
+    return sim
+}
+
+fn bench_pass(c: &mut Criterion, name: &str, pass: fn(&mut ParticleSim, u64)) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(20);
+    for scene_name in SCENES {
+        for size in SIZES {
+            let sim = scene(scene_name, size);
+            group.bench_with_input(BenchmarkId::new(scene_name, size), &sim, |b, sim| {
+                b.iter_batched(|| sim.clone(), |mut sim| { pass(&mut sim, 1); sim }, BatchSize::LargeInput)
+            });
+        }
+    }
+    group.finish();
+}
+
+fn passes(c: &mut Criterion) {
+    bench_pass(c, "simulate_sand", ParticleSim::simulate_sand);
+    bench_pass(c, "simulate_liquids", ParticleSim::simulate_liquids);
+    bench_pass(c, "simulate_gasses", ParticleSim::simulate_gasses);
+    bench_pass(c, "simulate_heat", ParticleSim::simulate_heat);
+    bench_pass(c, "simulate_heat_simplified", ParticleSim::simulate_heat_simplified);
+}
+
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_pixels");
+    group.sample_size(20);
+    for scene_name in SCENES {
+        for size in SIZES {
+            let mut sim = scene(scene_name, size);
+            group.bench_function(BenchmarkId::new(scene_name, size), |b| b.iter(|| black_box(sim.render_pixels())));
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, passes, render);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+use crate::particle_sim::{ParticleSim, ParticleType};
+use crate::replay::Edit;
+
+// the world outside the grid has a temperature too. materials with a cooling rate lose (or gain) heat to it every
+// heat pass, newton's law of cooling style, instead of only ever evening out with their neighbours.
+// nothing cools by default, so a simulation that never sets a rate behaves like there's no ambient at all
+impl ParticleSim {
+    pub fn set_ambient_temperature(&mut self, temperature: u32) {
+        self.record(Edit::SetAmbientTemperature { temperature });
+        self.ambient_temperature = temperature;
+        self.wake_all();
+    }
+
+    pub fn ambient_temperature(&self) -> u32 {
+        return self.ambient_temperature
+    }
+
+    // rate is the part of the gap to the ambient temperature a particle of this type closes every heat pass,
+    // 0.0 turns it off (the default) and 1.0 snaps straight to ambient. gasses don't cool in simulate_heat_simplified
+    pub fn set_cooling_rate(&mut self, particle_type: &ParticleType, rate: f32) {
+        let rate = if rate > 0.0 { rate.min(1.0) } else { 0.0 };
+        self.record(Edit::SetCoolingRate { particle_type: *particle_type, rate });
+        if rate > 0.0 {
+            self.cooling.insert(particle_type.id, rate);
+        } else {
+            self.cooling.remove(&particle_type.id);
+        }
+        self.wake_all();
+    }
+
+    pub fn get_cooling_rate(&self, particle_type: &ParticleType) -> f32 {
+        return *self.cooling.get(&particle_type.id).unwrap_or(&0.0)
+    }
+}
@@ -0,0 +1,93 @@
+// colour of a blackbody by temperature, in sRGB with the brightest channel at 255. the points are mitchell charity's
+// blackbody table (CIE 1964 10 degree observer), and the colour in between is blended so it doesn't jump from one
+// step to the next. the two below 1000 K aren't in the table, they just fade the red out
+const TABLE: [(f32, [u8; 3]); 41] = [
+    (600.0, [255, 0, 0]),
+    (800.0, [255, 25, 0]),
+    (1000.0, [255, 56, 0]),
+    (1200.0, [255, 83, 0]),
+    (1400.0, [255, 101, 0]),
+    (1600.0, [255, 115, 0]),
+    (1800.0, [255, 126, 0]),
+    (2000.0, [255, 137, 18]),
+    (2200.0, [255, 147, 44]),
+    (2400.0, [255, 157, 63]),
+    (2600.0, [255, 165, 79]),
+    (2800.0, [255, 173, 94]),
+    (3000.0, [255, 180, 107]),
+    (3200.0, [255, 187, 120]),
+    (3400.0, [255, 193, 132]),
+    (3600.0, [255, 199, 143]),
+    (3800.0, [255, 204, 153]),
+    (4000.0, [255, 209, 163]),
+    (4200.0, [255, 213, 173]),
+    (4400.0, [255, 217, 182]),
+    (4600.0, [255, 221, 190]),
+    (4800.0, [255, 225, 198]),
+    (5000.0, [255, 228, 206]),
+    (5200.0, [255, 232, 213]),
+    (5400.0, [255, 235, 220]),
+    (5600.0, [255, 238, 227]),
+    (5800.0, [255, 240, 233]),
+    (6000.0, [255, 243, 239]),
+    (6200.0, [255, 245, 245]),
+    (6400.0, [255, 248, 251]),
+    (6600.0, [254, 249, 255]),
+    (6800.0, [249, 246, 255]),
+    (7000.0, [245, 243, 255]),
+    (7200.0, [240, 241, 255]),
+    (7400.0, [237, 239, 255]),
+    (7600.0, [233, 237, 255]),
+    (7800.0, [230, 235, 255]),
+    (8000.0, [227, 233, 255]),
+    (8500.0, [220, 229, 255]),
+    (9000.0, [214, 225, 255]),
+    (10000.0, [204, 219, 255]),
+];
+
+// how bright the glow is at a temperature. below start_temperature nothing glows, at full_temperature the blackbody
+// colour is added at full strength, and it keeps getting brighter past that (which ends up white once it clamps).
+// exponent bends the ramp in between, 1.0 is a straight line and bigger keeps things dark for longer
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glow {
+    pub start_temperature: f32,
+    pub full_temperature: f32,
+    pub exponent: f32,
+}
+
+impl Default for Glow {
+    fn default() -> Glow {
+        return Glow { start_temperature: 600.0, full_temperature: 3000.0, exponent: 1.0 }
+    }
+}
+
+impl Glow {
+    pub fn intensity(&self, temperature: f32) -> f32 {
+        if temperature <= self.start_temperature {
+            return 0.0
+        }
+        let span = (self.full_temperature - self.start_temperature).max(f32::MIN_POSITIVE);
+        return ((temperature - self.start_temperature) / span).powf(self.exponent)
+    }
+
+    // what gets added on top of a particle's own colour
+    pub fn color(&self, temperature: f32) -> [f32; 3] {
+        let intensity = self.intensity(temperature);
+        return blackbody_color(temperature).map(|c| c * intensity)
+    }
+}
+
+// 0.0 to 255.0 per channel. colder than the table is its reddest end, hotter than it stays at 10000 K's blue white,
+// the hue barely changes past that anyway
+pub fn blackbody_color(temperature: f32) -> [f32; 3] {
+    let next = TABLE.iter().position(|(t, _)| *t > temperature).unwrap_or(TABLE.len());
+    if next == 0 {
+        return TABLE[0].1.map(|c| c as f32)
+    }
+    if next == TABLE.len() {
+        return TABLE[TABLE.len() - 1].1.map(|c| c as f32)
+    }
+    let ((t0, a), (t1, b)) = (TABLE[next - 1], TABLE[next]);
+    let blend = (temperature - t0) / (t1 - t0);
+    return [0, 1, 2].map(|i| a[i] as f32 + (b[i] as f32 - a[i] as f32) * blend)
+}
@@ -0,0 +1,75 @@
+use crate::particle_sim::{Particle, ParticleSim};
+use crate::replay::Edit;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+// what the passes see past an edge of the grid. rigid bodies and the structure pass always treat every edge as a wall
+#[derive(Debug, Copy, Clone)]
+pub enum Boundary {
+    Wall, // nothing gets in or out and no heat crosses it, the default
+    Void(Particle), // anything can fall, flow or rise out and is gone for good, the spot it left gets this particle
+    Wrap, // the grid is a loop, leaving on one side comes back in on the other. always set on both opposite edges
+    Open(Particle), // an endless supply of this particle at its own temperature. it flows in, heat goes both ways,
+                    // and whatever the particle would make way for leaves the grid in exchange
+}
+
+impl Edge {
+    pub(crate) const ALL: [Edge; 4] = [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom];
+
+    fn opposite(&self) -> Edge {
+        return match self {
+            Edge::Left => Edge::Right,
+            Edge::Right => Edge::Left,
+            Edge::Top => Edge::Bottom,
+            Edge::Bottom => Edge::Top,
+        }
+    }
+}
+
+impl Boundary {
+    // the particle that's outside, for the boundaries that have one
+    pub(crate) fn particle(&self) -> Option<Particle> {
+        return match self {
+            Boundary::Void(particle) | Boundary::Open(particle) => Some(*particle),
+            _ => None,
+        }
+    }
+}
+
+impl ParticleSim {
+    // wrapping one edge wraps the opposite one too, and replacing a wrap on one side turns the other side into a wall
+    pub fn set_boundary(&mut self, edge: Edge, boundary: Boundary) {
+        self.record(Edit::SetBoundary { edge, boundary });
+        let opposite = edge.opposite();
+        if let Boundary::Wrap = boundary {
+            self.boundaries[opposite as usize] = Boundary::Wrap;
+        } else if let Boundary::Wrap = self.boundaries[edge as usize] {
+            self.boundaries[opposite as usize] = Boundary::Wall;
+        }
+        self.boundaries[edge as usize] = boundary;
+        self.wake_all();
+    }
+
+    pub fn set_all_boundaries(&mut self, boundary: Boundary) {
+        for edge in Edge::ALL {
+            self.set_boundary(edge, boundary);
+        }
+    }
+
+    pub fn boundary(&self, edge: Edge) -> Boundary {
+        return self.boundaries[edge as usize]
+    }
+
+    pub(crate) fn wraps(&self) -> (bool, bool) {
+        return (
+            matches!(self.boundaries[Edge::Left as usize], Boundary::Wrap),
+            matches!(self.boundaries[Edge::Top as usize], Boundary::Wrap),
+        )
+    }
+}
@@ -0,0 +1,87 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::error::SimError;
+use crate::particle_sim::{Particle, ParticleSim};
+
+// a copy of a particle that gets written back into the grid (and recorded, and wakes its chunk) when it's dropped,
+// the grid is stored in columns so there's no Particle in there to hand out a reference to. it goes back exactly as
+// it was changed, set_particle would sample the type's texture again and undo any change to color_noise
+pub struct ParticleMut<'a> {
+    sim: &'a mut ParticleSim,
+    x: usize,
+    y: usize,
+    original: Particle,
+    particle: Particle,
+}
+
+impl Deref for ParticleMut<'_> {
+    type Target = Particle;
+
+    fn deref(&self) -> &Particle {
+        return &self.particle
+    }
+}
+
+impl DerefMut for ParticleMut<'_> {
+    fn deref_mut(&mut self) -> &mut Particle {
+        return &mut self.particle
+    }
+}
+
+impl Drop for ParticleMut<'_> {
+    fn drop(&mut self) {
+        if self.particle != self.original {
+            self.sim.write_particle(self.x, self.y, self.particle);
+        }
+    }
+}
+
+impl ParticleSim {
+    fn check(&self, x: i64, y: i64) -> Result<(usize, usize), SimError> {
+        if x < 0 || y < 0 || !self.particle_exists(x as usize, y as usize) {
+            return Err(SimError::OutOfBounds { x, y, width: self.width, height: self.height })
+        }
+        return Ok((x as usize, y as usize))
+    }
+
+    // particle_at without the panic
+    pub fn get(&self, x: usize, y: usize) -> Option<Particle> {
+        return self.get_signed(x as i64, y as i64)
+    }
+
+    pub fn get_signed(&self, x: i64, y: i64) -> Option<Particle> {
+        return self.check(x, y).ok().map(|(x, y)| self.particle_at(x, y))
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<ParticleMut<'_>> {
+        return self.get_mut_signed(x as i64, y as i64)
+    }
+
+    pub fn get_mut_signed(&mut self, x: i64, y: i64) -> Option<ParticleMut<'_>> {
+        let (x, y) = self.check(x, y).ok()?;
+        let particle = self.particle_at(x, y);
+        return Some(ParticleMut { sim: self, x, y, original: particle, particle })
+    }
+
+    // set_particle that says so when the coordinates or the particle's type are off instead of quietly going ahead
+    pub fn try_set(&mut self, x: usize, y: usize, particle: Particle) -> Result<(), SimError> {
+        return self.try_set_signed(x as i64, y as i64, particle)
+    }
+
+    pub fn try_set_signed(&mut self, x: i64, y: i64, particle: Particle) -> Result<(), SimError> {
+        let (x, y) = self.check(x, y)?;
+        particle.particle_type.validate()?;
+        self.set_particle(x, y, particle);
+        return Ok(())
+    }
+
+    pub fn try_set_energy(&mut self, x: usize, y: usize, energy: u64) -> Result<(), SimError> {
+        return self.try_set_energy_signed(x as i64, y as i64, energy)
+    }
+
+    pub fn try_set_energy_signed(&mut self, x: i64, y: i64, energy: u64) -> Result<(), SimError> {
+        let (x, y) = self.check(x, y)?;
+        self.set_particle_energy(x, y, energy);
+        return Ok(())
+    }
+}
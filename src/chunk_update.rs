@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+
+use half::f16;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::boundary::{Boundary, Edge};
+use crate::chunks::CHUNK_SIZE;
+use crate::particle_sim::{Particle, ParticleSim, ParticleType, Phases};
+use crate::storage::RawStorage;
+
+const MIX_OFFSETS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Pass {
+    Movement,
+    Sand,
+    Liquids,
+    Gasses,
+    Heat,
+    HeatSimplified,
+}
+
+// what's past a Void or Open edge, worked out once per pass
+#[derive(Copy, Clone)]
+struct Outside {
+    particle: Particle,
+    type_index: u16,
+    state: u8,
+    temperature: f64,
+    density: f32,
+    open: bool, // false for a void, which takes anything and gives nothing back
+}
+
+// what a position next to a cell turns out to be once the boundaries are taken into account
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Spot {
+    Cell(usize, usize),
+    Outside(usize), // past the edge with this index, see Edge
+    Wall,
+}
+
+// everything one chunk needs to run a pass on its own: the grid, its own random generator and a list of cells it
+// changed so their chunks can be woken up once the phase is over. a chunk where nothing changed but a roll held a
+// move back is pending, it has to stay awake to roll again or it'd fall asleep halfway through
+pub(crate) struct ChunkUpdate<'a> {
+    grid: RawStorage,
+    width: usize,
+    height: usize,
+    miscibility: &'a HashMap<(u32, u32), f32>,
+    cooling: &'a HashMap<u32, f32>,
+    ambient_temperature: f64,
+    boundaries: [Boundary; 4],
+    outside: [Option<Outside>; 4],
+    stamp: u32, // particles with this moved_stamp already moved during this pass
+    rng: StdRng,
+    woken: Vec<(usize, usize)>,
+    pending: bool,
+}
+
+// splitmix64, turns the pass seed and the chunk index into an independent seed for every chunk
+fn chunk_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return z ^ (z >> 31)
+}
+
+impl ParticleSim {
+    // runs a pass over every awake chunk in checkerboard phases. within a phase no two chunks can reach the
+    // same cell, so the order they run in doesn't matter and the parallel mode gives the same result as the serial one.
+    // that's also why the chunks can share the grid through raw pointers: an update only ever touches cells at most
+    // two away from its own chunk, and chunks in the same phase are a whole chunk apart. across a wrapped edge the
+    // first chunk of a row or column would touch the last one, so those get a phase of their own
+    pub(crate) fn run_chunked(&mut self, pass: Pass, t: u64) {
+        let pass_seed = self.rng().gen::<u64>();
+        let stamp = self.pass_stamp;
+        let (width, height) = (self.width, self.height);
+        let (cols, rows) = (self.chunks.cols, self.chunks.rows);
+        let (wrap_x, wrap_y) = self.wraps();
+        let class = |c: usize, count: usize, wraps: bool| if wraps && count > 1 && c == 0 { 2 } else { c % 2 };
+
+        let boundaries = self.boundaries;
+        self.particles.make_room(Edge::ALL.len());
+        let outside = Edge::ALL.map(|edge| {
+            let boundary = boundaries[edge as usize];
+            return boundary.particle().map(|particle| {
+                let open = matches!(boundary, Boundary::Open(_));
+                Outside {
+                    particle,
+                    type_index: self.particles.palette_index(&particle.particle_type),
+                    state: if open { particle.get_state() } else { 3 },
+                    temperature: particle.get_temperature_f64(),
+                    density: if open { particle.get_density() } else { f32::MIN_POSITIVE },
+                    open,
+                }
+            })
+        });
+
+        for phase in 0..9 {
+            let chunks: Vec<(usize, usize)> = (0..rows)
+                .flat_map(|cy| (0..cols).map(move |cx| (cx, cy)))
+                .filter(|(cx, cy)| {
+                    class(*cx, cols, wrap_x) + 3 * class(*cy, rows, wrap_y) == phase && self.chunks.is_active(*cx, *cy)
+                })
+                .collect();
+            if chunks.is_empty() {
+                continue
+            }
+
+            let grid = self.particles.raw();
+            let miscibility = &self.miscibility;
+            let (cooling, ambient_temperature) = (&self.cooling, self.ambient_temperature as f64);
+            let run = |(cx, cy): &(usize, usize)| {
+                let mut update = ChunkUpdate {
+                    grid,
+                    width,
+                    height,
+                    miscibility,
+                    cooling,
+                    ambient_temperature,
+                    boundaries,
+                    outside,
+                    stamp,
+                    rng: StdRng::seed_from_u64(chunk_seed(pass_seed, (cx + cy * cols) as u64)),
+                    woken: Vec::new(),
+                    pending: false,
+                };
+                update.run(pass, *cx, *cy, t);
+                (update.woken, update.pending)
+            };
+
+            #[cfg(feature = "rayon")]
+            let results: Vec<(Vec<(usize, usize)>, bool)> = if self.parallel {
+                chunks.par_iter().map(run).collect()
+            } else {
+                chunks.iter().map(run).collect()
+            };
+            #[cfg(not(feature = "rayon"))]
+            let results: Vec<(Vec<(usize, usize)>, bool)> = chunks.iter().map(run).collect();
+
+            for ((cx, cy), (woken, pending)) in chunks.into_iter().zip(results) {
+                for (x, y) in woken {
+                    self.wake(x, y);
+                }
+                if pending {
+                    self.chunks.keep_awake(cx, cy);
+                }
+            }
+        }
+    }
+
+    // with this on the chunks of each checkerboard phase are spread over rayon's thread pool
+    #[cfg(feature = "rayon")]
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+}
+
+impl<'a> ChunkUpdate<'a> {
+    fn run(&mut self, pass: Pass, cx: usize, cy: usize, t: u64) {
+        let reverse = t % 2 == 0;
+        let (x0, x1) = (cx * CHUNK_SIZE, ((cx + 1) * CHUNK_SIZE).min(self.width));
+        let (y0, y1) = (cy * CHUNK_SIZE, ((cy + 1) * CHUNK_SIZE).min(self.height));
+        let xs = move |forward: bool| (0..x1 - x0).map(move |i| if forward { x0 + i } else { x1 - i - 1 });
+        let open = self.outside.iter().any(|outside| outside.is_some_and(|o| o.open));
+
+        match pass {
+            Pass::Movement => {
+                for y in (y0..y1).rev() {
+                    for x in xs(reverse) {
+                        match self.state(x, y) {
+                            1 => self.update_sand(x, y),
+                            2 => self.update_liquid(x, y),
+                            3 => self.update_gas(x, y),
+                            _ => {}
+                        }
+                        if open {
+                            self.inflow(x, y, pass);
+                        }
+                    }
+                }
+            }
+            Pass::Sand | Pass::Liquids => {
+                for y in (y0..y1).rev() {
+                    for x in xs(reverse) {
+                        if pass == Pass::Sand {
+                            self.update_sand(x, y);
+                        } else {
+                            self.update_liquid(x, y);
+                        }
+                        if open {
+                            self.inflow(x, y, pass);
+                        }
+                    }
+                }
+            }
+            Pass::Gasses => {
+                for y in y0..y1 {
+                    for x in xs(!reverse) {
+                        self.update_gas(x, y);
+                        if open {
+                            self.inflow(x, y, pass);
+                        }
+                    }
+                }
+            }
+            Pass::Heat | Pass::HeatSimplified => {
+                for x in x0..x1 {
+                    for y in y0..y1 {
+                        self.update_heat(x, y, pass == Pass::HeatSimplified);
+                    }
+                }
+            }
+        }
+    }
+
+    // resolves one coordinate against the pair of edges it could be past
+    fn across(&self, v: i32, size: usize, low: Edge, high: Edge) -> Result<usize, Spot> {
+        let edge = if v < 0 {
+            low
+        } else if v >= size as i32 {
+            high
+        } else {
+            return Ok(v as usize)
+        };
+        return match self.boundaries[edge as usize] {
+            Boundary::Wall => Err(Spot::Wall),
+            Boundary::Wrap => Ok(v.rem_euclid(size as i32) as usize),
+            _ => Err(Spot::Outside(edge as usize)),
+        }
+    }
+
+    fn spot(&self, x: i32, y: i32) -> Spot {
+        let x = match self.across(x, self.width, Edge::Left, Edge::Right) {
+            Ok(x) => x,
+            Err(spot) => return spot,
+        };
+        let y = match self.across(y, self.height, Edge::Top, Edge::Bottom) {
+            Ok(y) => y,
+            Err(spot) => return spot,
+        };
+        return Spot::Cell(x, y)
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        debug_assert!(x < self.width && y < self.height);
+        return x + y * self.width
+    }
+
+    fn particle_type(&self, x: usize, y: usize) -> &'a ParticleType {
+        return unsafe { self.grid.particle_type(self.index(x, y)) }
+    }
+
+    fn phases(&self, x: usize, y: usize) -> &'a Phases {
+        return unsafe { self.grid.phases(self.index(x, y)) }
+    }
+
+    fn energy(&self, x: usize, y: usize) -> u64 {
+        return unsafe { self.grid.energy(self.index(x, y)) }
+    }
+
+    fn temperature(&self, x: usize, y: usize) -> f64 {
+        let i = self.index(x, y);
+        return unsafe { self.grid.phases(i).temperature(self.grid.energy(i)) }
+    }
+
+    fn state(&self, x: usize, y: usize) -> u8 {
+        let i = self.index(x, y);
+        return unsafe { self.grid.phases(i).state(self.grid.energy(i), self.grid.crumbled(i)) }
+    }
+
+    fn density(&self, x: usize, y: usize) -> f32 {
+        let i = self.index(x, y);
+        return unsafe { self.grid.phases(i).density(self.grid.energy(i), self.grid.crumbled(i)) }
+    }
+
+    // None for a wall, which nothing can move into
+    fn spot_state(&self, spot: Spot) -> Option<u8> {
+        return match spot {
+            Spot::Cell(x, y) => Some(self.state(x, y)),
+            Spot::Outside(edge) => self.outside[edge].map(|outside| outside.state),
+            Spot::Wall => None,
+        }
+    }
+
+    fn spot_density(&self, spot: Spot) -> f32 {
+        return match spot {
+            Spot::Cell(x, y) => self.density(x, y),
+            Spot::Outside(edge) => self.outside[edge].map_or(f32::MAX, |outside| outside.density),
+            Spot::Wall => f32::MAX,
+        }
+    }
+
+    // liquids and gasses can be pushed aside
+    fn is_fluid(&self, spot: Spot) -> bool {
+        return self.spot_state(spot).is_some_and(|state| state > 1)
+    }
+
+    fn can_displace(&self, x: usize, y: usize, spot: Spot) -> bool {
+        return self.is_fluid(spot) && self.density(x, y) > self.spot_density(spot)
+    }
+
+    fn set_velocity(&mut self, x: usize, y: usize, vx: i32, vy: i32) {
+        let i = self.index(x, y);
+        unsafe { self.grid.set_velocity(i, [f16::from_f32(vx as f32), f16::from_f32(vy as f32)]) };
+    }
+
+    fn set_moved(&mut self, x: usize, y: usize) {
+        let i = self.index(x, y);
+        unsafe { self.grid.set_moved_stamp(i, self.stamp) };
+    }
+
+    fn has_moved(&self, x: usize, y: usize) -> bool {
+        return unsafe { self.grid.moved_stamp(self.index(x, y)) } == self.stamp
+    }
+
+    // a particle whose turn it is that hasn't been moved yet this pass starts out standing still
+    fn reset_velocity(&mut self, x: usize, y: usize) {
+        if !self.has_moved(x, y) {
+            self.set_velocity(x, y, 0, 0);
+        }
+    }
+
+    // moves the particle at (x, y) one step, swapping it with whatever is there. both of them count as having moved.
+    // moving past a void or open edge takes the particle out of the grid and lets the outside fill its spot
+    fn move_particle(&mut self, x: usize, y: usize, xo: i32, yo: i32) {
+        self.set_moved(x, y);
+        match self.spot(x as i32 + xo, y as i32 + yo) {
+            Spot::Cell(xi, yi) => {
+                let (i, j) = (self.index(x, y), self.index(xi, yi));
+                unsafe { self.grid.swap(i, j) };
+                self.set_velocity(xi, yi, xo, yo);
+                self.set_velocity(x, y, -xo, -yo);
+                self.woken.push((x, y));
+                self.woken.push((xi, yi));
+            }
+            Spot::Outside(edge) => self.let_in(x, y, edge, 0, 0),
+            Spot::Wall => {}
+        }
+    }
+
+    // replaces (x, y) with the particle from past the edge, whatever was there is gone
+    fn let_in(&mut self, x: usize, y: usize, edge: usize, vx: i32, vy: i32) {
+        if let Some(outside) = self.outside[edge] {
+            let i = self.index(x, y);
+            unsafe { self.grid.set(i, outside.type_index, &outside.particle.textured(x as i64, y as i64)) };
+            self.set_moved(x, y);
+            self.set_velocity(x, y, vx, vy);
+            self.woken.push((x, y));
+        }
+    }
+
+    // an open edge pushes its particle into the grid the same way it would move if it were inside. whatever is in
+    // the way gets pushed out in exchange
+    fn inflow(&mut self, x: usize, y: usize, pass: Pass) {
+        let edges = [
+            (Edge::Left, x == 0, 1, 0),
+            (Edge::Right, x == self.width - 1, -1, 0),
+            (Edge::Top, y == 0, 0, 1),
+            (Edge::Bottom, y == self.height - 1, 0, -1),
+        ];
+        for (edge, on_edge, xo, yo) in edges {
+            let outside = match self.outside[edge as usize] {
+                Some(outside) if on_edge && outside.open => outside,
+                _ => continue,
+            };
+            let moves = match (outside.state, pass) {
+                (1, Pass::Sand | Pass::Movement) => yo == 1,
+                (2, Pass::Liquids | Pass::Movement) => yo >= 0,
+                (3, Pass::Gasses | Pass::Movement) => yo <= 0,
+                _ => false,
+            };
+            if moves && !self.has_moved(x, y) && self.is_fluid(Spot::Cell(x, y)) && outside.density > self.density(x, y) {
+                self.let_in(x, y, edge as usize, xo, yo);
+            }
+        }
+    }
+
+    fn write_energy(&mut self, x: usize, y: usize, energy: u64) {
+        if self.energy(x, y) != energy {
+            let i = self.index(x, y);
+            unsafe { self.grid.set_energy(i, energy) };
+            self.woken.push((x, y));
+        }
+    }
+
+    fn get_miscibility(&self, a: &ParticleType, b: &ParticleType) -> f32 {
+        if a.id == b.id {
+            return 1.0
+        }
+        return *self.miscibility.get(&(a.id.min(b.id), a.id.max(b.id))).unwrap_or(&0.0)
+    }
+
+    // how many of the 8 neighbours of (x, y) are the given type, not counting (skip_x, skip_y)
+    fn count_same_neighbors(&self, x: usize, y: usize, id: u32, skip_x: usize, skip_y: usize) -> u8 {
+        let mut count = 0;
+        for (xo, yo) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+            if let Spot::Cell(xn, yn) = self.spot(x as i32 + xo, y as i32 + yo) {
+                if (xn != skip_x || yn != skip_y) && self.particle_type(xn, yn).id == id {
+                    count += 1;
+                }
+            }
+        }
+        return count
+    }
+
+    // extra rules for liquids on top of the density check: immiscible liquids only ever trade places vertically (so they layer
+    // instead of interleaving), and surface tension keeps a liquid from stepping sideways away from its own kind
+    fn liquid_move_allowed(&mut self, x: usize, y: usize, xo: i32, yo: i32) -> bool {
+        if yo != 0 {
+            return true
+        }
+        let particle = self.particle_type(x, y);
+        let (target, target_state, target_cell) = match self.spot(x as i32 + xo, y as i32 + yo) {
+            Spot::Cell(xi, yi) => (self.particle_type(xi, yi), self.state(xi, yi), Some((xi, yi))),
+            Spot::Outside(edge) => match self.outside[edge] {
+                Some(outside) if outside.open => (unsafe { self.grid.palette_type(outside.type_index) }, outside.state, None),
+                _ => return true,
+            },
+            Spot::Wall => return false,
+        };
+        if target.id == particle.id {
+            return true
+        }
+        if target_state == 2 && self.get_miscibility(particle, target) == 0.0 {
+            return false
+        }
+        if particle.surface_tension > 0.0 {
+            let id = particle.id;
+            let around_target = target_cell.map_or(0, |(xi, yi)| self.count_same_neighbors(xi, yi, id, x, y));
+            if around_target < self.count_same_neighbors(x, y, id, x, y)
+                && self.rng.gen::<f32>() < particle.surface_tension {
+                self.pending = true;
+                return false
+            }
+        }
+        return true
+    }
+
+    // miscible liquids slowly diffuse into each other by swapping with a random touching neighbour. when that
+    // doesn't happen but could have, the chunk is pending
+    fn mix_liquids(&mut self, x: usize, y: usize) {
+        if self.try_mix(x, y) {
+            return
+        }
+        if self.miscibility.is_empty() {
+            return
+        }
+        let particle = self.particle_type(x, y);
+        let could_mix = MIX_OFFSETS.into_iter().any(|(xo, yo)| match self.spot(x as i32 + xo, y as i32 + yo) {
+            Spot::Cell(xi, yi) => {
+                let neighbor = self.particle_type(xi, yi);
+                self.state(xi, yi) == 2 && neighbor.id != particle.id && self.get_miscibility(particle, neighbor) > 0.0
+            }
+            _ => false,
+        });
+        self.pending |= could_mix;
+    }
+
+    fn try_mix(&mut self, x: usize, y: usize) -> bool {
+        let (xo, yo) = MIX_OFFSETS[self.rng.gen_range(0..MIX_OFFSETS.len())];
+        let Spot::Cell(xi, yi) = self.spot(x as i32 + xo, y as i32 + yo) else { return false };
+        if self.has_moved(xi, yi) || self.state(xi, yi) != 2 {
+            return false
+        }
+        let particle = self.particle_type(x, y);
+        let neighbor = self.particle_type(xi, yi);
+        if particle.id == neighbor.id {
+            return false
+        }
+        if self.rng.gen::<f32>() < self.get_miscibility(particle, neighbor) {
+            self.set_moved(xi, yi);
+            self.move_particle(x, y, xo, yo);
+            return true
+        }
+        return false
+    }
+
+    fn update_sand(&mut self, x: usize, y: usize) {
+        if self.state(x, y) == 1 {
+            self.reset_velocity(x, y);
+            let mut xoffsets = [0, 1, -1];
+            if self.rng.gen::<bool>(){
+                xoffsets = [0, -1, 1];
+            }
+            for (i, xo) in xoffsets.into_iter().enumerate() {
+                let target = self.spot(x as i32 + xo, y as i32 + 1);
+                if !self.has_moved(x, y)
+                    && self.can_displace(x, y, target)
+                    && (i == 0 || self.is_fluid(self.spot(x as i32 + xo, y as i32)))
+                {
+                    self.move_particle(x, y, xo, 1);
+                    return
+                }
+            }
+        }
+    }
+
+    fn update_liquid(&mut self, x: usize, y: usize) {
+        if self.state(x, y) == 2 {
+            self.reset_velocity(x, y);
+            let mut xoffsets = [0, 1, -1, 1, -1];
+            if self.rng.gen::<bool>(){
+                xoffsets = [0, -1, 1, -1, 1];
+            }
+            let yoffsets = [1, 1, 1, 0, 0];
+
+            let mut highest_desity_delta = 0.0;
+            let mut highest_desity_index: usize = 0;
+            for i in 0..xoffsets.len(){
+                let target = self.spot(x as i32 + xoffsets[i], y as i32 + yoffsets[i]);
+                if !self.has_moved(x, y)
+                    && self.can_displace(x, y, target)
+                    && self.liquid_move_allowed(x, y, xoffsets[i], yoffsets[i])
+                {
+                    if i == 0 {
+                        self.move_particle(x, y, xoffsets[i], yoffsets[i]);
+                        return
+                    } else if self.is_fluid(self.spot(x as i32 + xoffsets[i], y as i32))
+                      &&  highest_desity_delta < self.spot_density(target){
+                        highest_desity_delta = self.spot_density(target);
+                        highest_desity_index = i;
+                    }
+                }
+            }
+            if highest_desity_delta > 0.0{
+                self.move_particle(x, y, xoffsets[highest_desity_index], yoffsets[highest_desity_index]);
+            } else if !self.has_moved(x, y) {
+                self.mix_liquids(x, y);
+            }
+        }
+    }
+
+    fn update_gas(&mut self, x: usize, y: usize) {
+        if self.state(x, y) == 3 {
+            self.reset_velocity(x, y);
+            let mut xoffsets = [0, 1, -1, 1, -1];
+            if self.rng.gen::<bool>(){
+                xoffsets = [0, -1, 1, -1, 1];
+            }
+            let yoffsets = [-1, -1, -1, 0, 0];
+
+            let mut highest_desity_delta = 0.0;
+            let mut highest_desity_index: usize = 0;
+            for i in 0..xoffsets.len(){
+                let target = self.spot(x as i32 + xoffsets[i], y as i32 + yoffsets[i]);
+                if !self.has_moved(x, y) && self.can_displace(x, y, target) {
+                    if i == 0 {
+                        self.move_particle(x, y, xoffsets[i], yoffsets[i]);
+                        return
+                    } else if self.is_fluid(self.spot(x as i32 + xoffsets[i], y as i32))
+                      &&  highest_desity_delta < self.spot_density(target){
+                        highest_desity_delta = self.spot_density(target);
+                        highest_desity_index = i;
+                    }
+                }
+            }
+            if highest_desity_delta > 0.0{
+                self.move_particle(x, y, xoffsets[highest_desity_index], yoffsets[highest_desity_index]);
+            }
+        }
+    }
+
+    // simplified leaves gasses out of the exchange, they neither give nor take heat. an open edge is an endless
+    // supply of its particle at its temperature, so heat crosses it without changing the outside.
+    // how much heat flows goes by the lighter of the two cells, so a cell of steam next to iron warms quickly
+    // but can't overshoot, and the iron barely notices. after that the cell cools towards the ambient temperature
+    fn update_heat(&mut self, x: usize, y: usize, simplified: bool) {
+        let (particle_type, phases) = (self.particle_type(x, y), self.phases(x, y));
+        let (energy, temperature) = (self.energy(x, y), self.temperature(x, y));
+        let state = self.state(x, y);
+
+        if !simplified || state != 3 {
+            let capacity = phases.heat_per_kelvin(state);
+            let xoffsets = [-1, -1, -1, 0, 0, 1, 1, 1];
+            let yoffsets = [-1, 0, 1, -1, 1, -1, 0, 1];
+            let mut energy_moved: i128 = 0;
+
+            for i in 0..xoffsets.len(){
+                let spot = self.spot(x as i32 + xoffsets[i], y as i32 + yoffsets[i]);
+                let (neighbor_temperature, neighbor_type, neighbor_phases, neighbor_state) = match spot {
+                    Spot::Cell(xo, yo) => {
+                        (self.temperature(xo, yo), self.particle_type(xo, yo), self.phases(xo, yo), self.state(xo, yo))
+                    }
+                    Spot::Outside(edge) => match self.outside[edge] {
+                        Some(outside) if outside.open => unsafe {
+                            let index = outside.type_index;
+                            (outside.temperature, self.grid.palette_type(index), self.grid.palette_phases(index), outside.state)
+                        },
+                        _ => continue,
+                    },
+                    Spot::Wall => continue,
+                };
+                let temperature_delta = temperature - neighbor_temperature;
+                let thermal_mass = capacity.min(neighbor_phases.heat_per_kelvin(neighbor_state));
+                let resistance = 8.0 * (particle_type.heat_resistance as f64 + neighbor_type.heat_resistance as f64 + 1.0);
+                let transfer = (temperature_delta * thermal_mass / resistance) as i128;
+
+                energy_moved += transfer;
+                if let Spot::Cell(xo, yo) = spot {
+                    if !simplified || neighbor_state != 3 {
+                        let np_energy = self.energy(xo, yo) as i128 + transfer;
+                        self.write_energy(xo, yo, saturate(np_energy));
+                    }
+                }
+            }
+            let mut particle_energy = saturate(energy as i128 - energy_moved);
+
+            if let Some(rate) = self.cooling.get(&particle_type.id) {
+                let temperature = phases.temperature(particle_energy);
+                let cooled = temperature + (self.ambient_temperature - temperature) * *rate as f64;
+                particle_energy = phases.energy(cooled).round() as u64;
+            }
+            self.write_energy(x, y, particle_energy);
+        }
+    }
+}
+
+// energy can't go below nothing or above what fits, heat that would go past either end is lost
+fn saturate(energy: i128) -> u64 {
+    return energy.clamp(0, u64::MAX as i128) as u64
+}
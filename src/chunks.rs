@@ -0,0 +1,116 @@
+use crate::particle_sim::ParticleSim;
+
+pub const CHUNK_SIZE: usize = 32; // chunks are CHUNK_SIZE x CHUNK_SIZE particles
+const WAKE_TICKS: u8 = 2; // how many ticks a chunk stays awake after something in or next to it changed
+
+// splits the grid into chunks that go to sleep once nothing in them has changed for a while. the passes skip
+// sleeping chunks entirely, so a world that's mostly settled only costs as much as the part that's still moving
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkGrid {
+    pub(crate) cols: usize,
+    pub(crate) rows: usize,
+    timers: Vec<u8>, // ticks left until the chunk falls asleep, 0 means it's asleep
+    tick: Option<u64>,
+    // chunks that were awake or touched since the history last looked, nothing else can have changed
+    dirty: Vec<bool>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ChunkStats {
+    pub chunk_size: usize,
+    pub total: usize,
+    pub active: usize,
+}
+
+impl ChunkGrid {
+    pub(crate) fn new(width: usize, height: usize) -> ChunkGrid {
+        let cols = width.div_ceil(CHUNK_SIZE);
+        let rows = height.div_ceil(CHUNK_SIZE);
+        return ChunkGrid { cols, rows, timers: vec![WAKE_TICKS; cols * rows], tick: None, dirty: vec![true; cols * rows] }
+    }
+
+    // the first pass of a new tick counts every chunk's timer down
+    pub(crate) fn begin_tick(&mut self, t: u64) {
+        if self.tick == Some(t) {
+            return
+        }
+        self.tick = Some(t);
+        for (timer, dirty) in self.timers.iter_mut().zip(&mut self.dirty) {
+            *dirty |= *timer > 0;
+            *timer = timer.saturating_sub(1);
+        }
+    }
+
+    pub(crate) fn is_active(&self, cx: usize, cy: usize) -> bool {
+        return self.timers[cx + cy * self.cols] > 0
+    }
+
+    // wakes every chunk the particle at (x, y) or any of its neighbours is in
+    pub(crate) fn wake(&mut self, x: usize, y: usize) {
+        let (cx0, cx1) = (x.saturating_sub(1) / CHUNK_SIZE, ((x + 1) / CHUNK_SIZE).min(self.cols - 1));
+        let (cy0, cy1) = (y.saturating_sub(1) / CHUNK_SIZE, ((y + 1) / CHUNK_SIZE).min(self.rows - 1));
+        for cy in cy0..=cy1 {
+            for cx in cx0..=cx1 {
+                self.timers[cx + cy * self.cols] = WAKE_TICKS;
+                self.dirty[cx + cy * self.cols] = true;
+            }
+        }
+    }
+
+    // keeps a chunk awake without waking its neighbours, for a chunk that still has something left to do
+    pub(crate) fn keep_awake(&mut self, cx: usize, cy: usize) {
+        self.timers[cx + cy * self.cols] = WAKE_TICKS;
+        self.dirty[cx + cy * self.cols] = true;
+    }
+
+    pub(crate) fn wake_all(&mut self) {
+        self.timers.fill(WAKE_TICKS);
+        self.dirty.fill(true);
+    }
+
+    // marks the chunk (x, y) is in as changed without waking it, for changes the passes don't need to see
+    pub(crate) fn touch(&mut self, x: usize, y: usize) {
+        self.dirty[x / CHUNK_SIZE + y / CHUNK_SIZE * self.cols] = true;
+    }
+
+    // which chunks changed since the last call, by chunk index
+    pub(crate) fn take_dirty(&mut self) -> Vec<bool> {
+        return std::mem::replace(&mut self.dirty, vec![false; self.cols * self.rows])
+    }
+}
+
+impl ParticleSim {
+    pub fn chunk_stats(&self) -> ChunkStats {
+        let total = self.chunks.cols * self.chunks.rows;
+        let active = self.chunks.timers.iter().filter(|timer| **timer > 0).count();
+        return ChunkStats { chunk_size: CHUNK_SIZE, total, active }
+    }
+
+    // cx and cy are chunk coordinates, so (x / CHUNK_SIZE, y / CHUNK_SIZE) for a particle at (x, y)
+    pub fn is_chunk_active(&self, cx: usize, cy: usize) -> bool {
+        return cx < self.chunks.cols && cy < self.chunks.rows && self.chunks.is_active(cx, cy)
+    }
+
+    // anything that writes to `particles` directly instead of going through the setters has to call this,
+    // otherwise the change won't be simulated until something wakes that chunk up
+    pub fn wake_all(&mut self) {
+        self.chunks.wake_all();
+    }
+
+    pub(crate) fn wake(&mut self, x: usize, y: usize) {
+        self.chunks.wake(x, y);
+        // across a wrapped edge the neighbours are on the far side of the grid
+        let (wrap_x, wrap_y) = self.wraps();
+        let far_x = wrap_x && (x == 0 || x == self.width - 1);
+        let far_y = wrap_y && (y == 0 || y == self.height - 1);
+        if far_x {
+            self.chunks.wake(self.width - 1 - x, y);
+        }
+        if far_y {
+            self.chunks.wake(x, self.height - 1 - y);
+        }
+        if far_x && far_y {
+            self.chunks.wake(self.width - 1 - x, self.height - 1 - y);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// everything that can go wrong in here. the panicking versions of things (new, particle_at, ...) still exist,
+// this is what their try_ and checked counterparts return instead
+#[derive(Debug)]
+pub enum SimError {
+    OutOfBounds { x: i64, y: i64, width: usize, height: usize }, // the coordinates asked for and the size of the grid they missed
+    InvalidSize { width: usize, height: usize }, // also a World's window that isn't the size it was made with any more
+    InvalidMaterial { id: u32, reason: &'static str },
+    EnergyOverflow { temperature: u32, heat_capacity: u32 }, // the energy a cell of this material holds at that temperature doesn't fit in a u64
+    BufferTooSmall { needed: usize, len: usize }, // in bytes
+    Io(io::Error),
+    Format(String), // the bytes were read fine but aren't a valid simulation or replay
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            SimError::OutOfBounds { x, y, width, height } => write!(f, "({}, {}) is outside the {}x{} grid", x, y, width, height),
+            SimError::InvalidSize { width, height } => write!(f, "a {}x{} grid is empty, too big to hold or not the size it has to be", width, height),
+            SimError::InvalidMaterial { id, reason } => write!(f, "particle type {} is invalid: {}", id, reason),
+            SimError::EnergyOverflow { temperature, heat_capacity } => {
+                write!(f, "{} K with a heat capacity of {} is more energy than a particle can hold", temperature, heat_capacity)
+            }
+            SimError::BufferTooSmall { needed, len } => write!(f, "the buffer is {} bytes but needs to be at least {}", len, needed),
+            SimError::Io(error) => write!(f, "{}", error),
+            SimError::Format(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for SimError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return match self {
+            SimError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SimError {
+    fn from(error: io::Error) -> SimError {
+        return SimError::Io(error)
+    }
+}
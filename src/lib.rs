@@ -1,2 +1,23 @@
+#![allow(clippy::needless_return)]
+// % keeps it building on toolchains from before is_multiple_of
+#![allow(clippy::manual_is_multiple_of)]
+
+pub mod ambient;
+pub mod blackbody;
+pub mod boundary;
+pub mod checked;
+pub mod chunk_update;
+pub mod chunks;
+pub mod error;
 pub mod particle_sim;
+pub mod region;
+pub mod render;
+pub mod replay;
+pub mod rigid_body;
+pub mod serialize;
+pub mod snapshot;
+pub mod stats;
+pub mod storage;
+pub mod structure;
 pub mod texture;
+pub mod world;
@@ -1,4 +1,30 @@
-#[derive(Debug, Copy, Clone)]
+use std::collections::HashMap;
+
+use half::f16;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::blackbody::Glow;
+use crate::boundary::Boundary;
+use crate::chunk_update::Pass;
+use crate::chunks::ChunkGrid;
+use crate::error::SimError;
+use crate::render::RenderMode;
+use crate::replay::{Edit, Replay};
+use crate::rigid_body::RigidBody;
+use crate::snapshot::History;
+use crate::storage::ParticleStorage;
+use crate::texture::Texture;
+
+// energy is a fixed point number of joules with this many steps per joule, so microjoules. even a cell of gas takes
+// somewhere around 75,000 to 300,000 of them to warm by a kelvin, and a u64 still has room for plasma temperatures
+// in a cell of iron
+pub const ENERGY_PER_JOULE: u64 = 1_000_000;
+
+// 1 pixel = 5cm, so a cell holds 125 cm^3 of whatever's in it
+pub const CELL_VOLUME: f64 = 125.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct ParticleType {
     pub id: u32,
     pub vapor_color: [u8; 4], // red green blue and alpha each 1 byte. i'd love to spell it colour but well for some reason i am making this code internationally readable so color it is
@@ -9,8 +35,14 @@ pub struct ParticleType {
     pub gas_density: f32,
     pub melting_temperature: u16, // in Kelvin
     pub boiling_temperature: u16, // also Kelvin
-    pub heat_capacity: u32, // How much energy (in joules) is needed to raise the temperature of 1 kg of substance by 1 degree celcius
+    pub heat_capacity: u32, // How much energy (in joules) is needed to raise the temperature of 1 kg of substance by 1 degree celcius, a cell's mass comes from its density
     pub heat_resistance: u16, // arbitrary unit, the larger it is, the higher it is, the slower it transfers heat
+    pub surface_tension: f32, // 0.0 to 1.0, chance that a liquid refuses to move sideways to a spot with fewer neighbours of its own kind
+    pub rigid: bool, // solid particles of a rigid type that touch each other form a rigid body and fall together, other solids never move
+    pub anchored: bool, // holds up any solid connected to it, like bedrock. so does a wall at the edge of the grid
+    pub strength: u32, // how many particles a loose chunk of this can have and still fall in one piece, bigger chunks crumble into powder.
+                       // a rigid body also cracks on landing faster than strength / its cell count in cells per tick
+    pub texture: Option<Texture>, // picks color_noise for every particle of this type that gets placed, None leaves it alone
 
 //    ignition_temperature: u16, // you know the drill, but also no way to turn this off for now
 //    burning_energy: u16, // how much energy will the particle emit over it burning
@@ -18,21 +50,87 @@ pub struct ParticleType {
 //    max_durability: u16, // how strong the particle is, this includes burning
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Particle {
     pub particle_type: ParticleType,
-    pub energy: u32, // in Joules.
+    pub energy: u64, // in microjoules, see ENERGY_PER_JOULE
     pub color_noise: u8, // this gets subtracted from the color value
-    iterated_over: bool,
-    //    velocity: [f16; 2],
+    pub crumbled: bool, // a solid that lost its support and now falls like sand
+    pub velocity: [f16; 2], // cells moved in the last movement pass, x then y
+    pub(crate) moved_stamp: u32, // the pass this last moved in, see ParticleSim::pass_stamp
+    pub(crate) body: u32, // id of the rigid body this is a part of, 0 for none
+    pub(crate) body_cell: u32, // which cell of that body's shape this is
     //    durability: u16
 }
 
 #[derive(Debug, Clone)]
 pub struct ParticleSim {
-    pub particles: Vec<Particle>, // a single vector index to it by [x + y * width]
+    pub particles: ParticleStorage, // index it by x + y * width
     pub width: usize,
     pub height: usize,
+    pub(crate) miscibility: HashMap<(u32, u32), f32>, // keyed by (smaller id, larger id), pairs that aren't in here don't mix
+    pub(crate) ambient_temperature: u32,
+    pub(crate) cooling: HashMap<u32, f32>, // keyed by type id, types that aren't in here don't cool
+    pub(crate) bodies: HashMap<u32, RigidBody>,
+    pub(crate) next_body_id: u32,
+    pub(crate) seed: u64,
+    rng: StdRng, // every random decision the simulation makes comes from here, so the same seed gives the same run
+    pub(crate) last_tick: Option<u64>, // t of the last pass that ran, a replay carries on from the tick after it
+    pub(crate) passes: u64, // how many passes have run, edits get recorded as happening after this many
+    pub(crate) recording: Option<Replay>,
+    pub(crate) history: Option<History>,
+    pub(crate) chunks: ChunkGrid,
+    pub(crate) boundaries: [Boundary; 4], // indexed by Edge
+    pub(crate) background: [u8; 4], // what translucent particles are drawn over, see render.rs
+    pub(crate) glow: Glow,
+    // bumped by every pass. a particle that moves gets stamped with it so it doesn't move twice in the same pass,
+    // which saves going over the whole grid again afterwards to reset a flag
+    pub(crate) pass_stamp: u32,
+    #[cfg(feature = "rayon")]
+    pub(crate) parallel: bool,
+}
+
+impl ParticleType {
+    // a plain grey powder with everything else at a middle of the road value. meant to have the fields that matter
+    // filled in with struct update syntax, ParticleType { liquid_density: 1.0, ..ParticleType::new(id) }
+    pub fn new(id: u32) -> ParticleType {
+        return ParticleType {
+            id,
+            vapor_color: [128, 128, 128, 128],
+            liquid_color: [128, 128, 128, 255],
+            solid_color: [128, 128, 128, 255],
+            solid: false,
+            liquid_density: 1.0,
+            gas_density: 1.0,
+            melting_temperature: 1000,
+            boiling_temperature: 2000,
+            heat_capacity: 1000,
+            heat_resistance: 1,
+            surface_tension: 0.0,
+            rigid: false,
+            anchored: false,
+            strength: 100,
+            texture: None,
+        }
+    }
+
+    // the things the simulation can't work with. the try_ functions check this, the plain ones just do their best
+    pub fn validate(&self) -> Result<(), SimError> {
+        let reason = if self.heat_capacity == 0 {
+            "heat_capacity can't be 0"
+        } else if self.melting_temperature > self.boiling_temperature {
+            "melting_temperature is above boiling_temperature"
+        } else if !(self.liquid_density.is_finite() && self.liquid_density > 0.0 && self.gas_density.is_finite() && self.gas_density > 0.0) {
+            "densities have to be positive"
+        } else if !(0.0..=1.0).contains(&self.surface_tension) {
+            "surface_tension has to be between 0.0 and 1.0"
+        } else if let Some(reason) = self.texture.and_then(|texture| texture.problem()) {
+            reason
+        } else {
+            return Ok(())
+        };
+        return Err(SimError::InvalidMaterial { id: self.id, reason })
+    }
 }
 
 impl Particle {
@@ -41,36 +139,20 @@ impl Particle {
             particle_type,
             energy: 0,
             color_noise: 128,
-            iterated_over: false,
+            crumbled: false,
+            velocity: [f16::ZERO; 2],
+            moved_stamp: 0,
+            body: 0,
+            body_cell: 0,
         }
     }
 
     pub fn get_state(&self) -> u8{
-        let temperature = self.get_temperature();
-        if temperature < self.particle_type.melting_temperature as u32 {
-            if self.particle_type.solid {
-                return 0
-            }
-            else {
-                return 1
-            }
-        }
-        else if temperature < self.particle_type.boiling_temperature as u32 {
-            return 2
-        }
-        return 3
+        return Phases::of(&self.particle_type).state(self.energy, self.crumbled)
     }
 
     pub fn get_density(&self) -> f32{
-
-
-        let mut density = self.particle_type.gas_density;
-        if self.get_state() == 2{
-            density = self.particle_type.liquid_density;
-        }
-        
-        density = density * (1.0 + ((self.get_temperature() as f32) / 500000000.0));
-        return density
+        return Phases::of(&self.particle_type).density(self.energy, self.crumbled)
     }
 
     pub fn set_noise_value(&mut self, value: u8) -> Particle {
@@ -78,479 +160,430 @@ impl Particle {
         return *self
     }
 
+    // the particle with color_noise from its type's texture at (x, y), unchanged if the type has none
+    pub fn textured(mut self, x: i64, y: i64) -> Particle {
+        if let Some(texture) = self.particle_type.texture {
+            self.color_noise = texture.sample(x, y);
+        }
+        return self
+    }
+
+    // whole kelvin, rounded down. get_temperature_f64 has the rest
     pub fn get_temperature(&self) -> u32 {
-        return self.energy / self.particle_type.heat_capacity as u32;
+        return Phases::of(&self.particle_type).temperature(self.energy) as u32
     }
 
+    // saturates at the most energy a particle can hold, try_set_temperature says so instead
     pub fn set_temperature(&mut self, temperature: u32) -> Particle {
-        self.energy = self.particle_type.heat_capacity * temperature;
+        return self.set_temperature_f64(temperature as f64)
+    }
+
+    pub fn try_set_temperature(&mut self, temperature: u32) -> Result<Particle, SimError> {
+        if Phases::of(&self.particle_type).energy(temperature as f64) >= u64::MAX as f64 {
+            return Err(SimError::EnergyOverflow { temperature, heat_capacity: self.particle_type.heat_capacity })
+        }
+        return Ok(self.set_temperature(temperature))
+    }
+
+    // good to about 1e-5 kelvin for a cell of steam, far finer for anything denser
+    pub fn get_temperature_f64(&self) -> f64 {
+        return Phases::of(&self.particle_type).temperature(self.energy)
+    }
+
+    // negative temperatures end up at 0, and anything too hot to store saturates like set_temperature.
+    // rounds the energy up so get_temperature gives back the same whole kelvin, float rounding can leave it a hair
+    // under otherwise. the step is at least as big as the gap between floats that size so it can't get stuck
+    pub fn set_temperature_f64(&mut self, temperature: f64) -> Particle {
+        let phases = Phases::of(&self.particle_type);
+        let mut energy = phases.energy(temperature).ceil() as u64;
+        while energy < u64::MAX && phases.temperature(energy) < temperature {
+            energy = energy.saturating_add((energy >> 52).max(1));
+        }
+        self.energy = energy;
         return *self
     }
 
     pub fn get_color(&self) -> [u8; 3]{
+        let [r, g, b, _] = self.get_color_over([0, 0, 0, 255], &Glow::default());
+        return [r, g, b]
+    }
 
-        //return [0, 0, 0];
+    // the particle laid over a background with its alpha ("over" compositing, straight alpha in and out). an opaque
+    // background gives an opaque colour, a transparent one gives the particle's own colour and alpha back so it can
+    // be blended over something else later. glow and noise go on top either way
+    pub fn get_color_over(&self, background: [u8; 4], glow: &Glow) -> [u8; 4]{
+        let phases = Phases::of(&self.particle_type);
+        let state = phases.state(self.energy, self.crumbled);
         let mut particle_base_color = self.particle_type.solid_color;
-        
 
-        if self.get_state() == 2 {
-            //particle_base_color = [0, 0, 0, 0];
+        if state == 2 {
             particle_base_color = self.particle_type.liquid_color;
         }
-        if self.get_state() == 3 {
+        if state == 3 {
             particle_base_color = self.particle_type.vapor_color;
         }
 
+        // everything in 0..255 steps, which keeps an opaque black background exactly the old darken-by-alpha
+        let (alpha, background_alpha) = (particle_base_color[3] as u32, background[3] as u32);
+        let out_alpha = alpha + background_alpha * (255 - alpha) / 255;
         for i in 0..3{
-            particle_base_color[i] = ((particle_base_color[i]as u32 * particle_base_color[3] as u32) / 255) as u8;
-        }
-        let mut out: [f32; 3]= [0.0, 0.0, 0.0];
-        let blackbody_lut: [[u8; 3]; 37] = [
-            [255,0,0],
-            [255,25,0],
-            [255,56,0],
-            [255,83,0],
-            [255,101,0],
-            [255,115,0],
-            [255,126,0],
-            [255,137,18],
-            [255,147,44],
-            [255,157,63],
-            [255,165,79],
-            [255,173,94],
-            [255,180,107],
-            [255,187,120],
-            [255,193,132],
-            [255,199,143],
-            [255,204,153],
-            [255,209,163],
-            [255,213,173],
-            [255,217,182],
-            [255,221,190],
-            [255,225,198],
-            [255,228,206],
-            [255,232,213],
-            [255,235,220],
-            [255,238,227],
-            [255,240,233],
-            [255,243,239],
-            [255,245,245],
-            [255,248,251],
-            [254,249,255],
-            [249,246,255],
-            [245,243,255],
-            [240,241,255],
-            [237,239,255],
-            [233,237,255],
-            [230,235,255],
-        ];
-
-        let mut temp_index = (self.get_temperature() as i32 -800)/200;
-
-        if temp_index < 0 {
-            temp_index = 0;
-        } else if temp_index > 34 {
-            temp_index = 36;
+            let premultiplied = particle_base_color[i] as u32 * alpha / 255 + background[i] as u32 * background_alpha * (255 - alpha) / (255 * 255);
+            particle_base_color[i] = (premultiplied * 255).checked_div(out_alpha).unwrap_or(0).min(255) as u8;
         }
 
+        let glow = glow.color(phases.temperature(self.energy) as f32);
+        let mut out: [f32; 3]= [0.0, 0.0, 0.0];
         for i in 0..3 {
-            out[i] = particle_base_color[i] as f32;
-            if self.get_temperature() > 600 {
-                out[i] += (blackbody_lut[temp_index as usize][i] as f32) * (self.get_temperature() as f32 / 3000.0);
-            }
+            out[i] = particle_base_color[i] as f32 + glow[i];
             out[i] += (self.color_noise as f32) - 128.0;
-            if out[i] > 255.0{
-                out[i] = 255.0;
-            }else if out[i] < 0.0{
-                out[i] = 0.0;
-            }
+            out[i] = out[i].clamp(0.0, 255.0);
         }
+        return [out[0] as u8, out[1] as u8, out[2] as u8, out_alpha as u8];
+    }
+}
 
-        //return blackbody_lut[temp_index as usize];
 
-        return [out[0] as u8, out[1] as u8, out[2] as u8];
+// everything the temperature and state of a cell depend on besides its energy. it takes a few divisions and a max
+// to work out, so the storage keeps one per palette entry instead of redoing it for every cell it looks at
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Phases {
+    pub condensed_per_kelvin: f64, // see heat_per_kelvin
+    pub gas_per_kelvin: f64,
+    pub melting: f64,
+    pub boiling: f64,
+    pub melt: f64, // the energy the liquid phase starts at
+    pub boil: f64, // and the gas phase
+    pub solid: bool,
+    pub liquid_density: f32,
+    pub gas_density: f32,
+}
 
+// energy to warm a whole cell of this by 1 K, specific heat times the cell's mass. a solid or powder is about as
+// dense as its liquid, so everything below the boiling point goes by liquid_density and only a gas by gas_density.
+// a heat capacity or density of 0 doesn't validate, it's kept just above 0 here rather than dividing by zero mid pass
+fn heat_per_kelvin(particle_type: &ParticleType, gas: bool) -> f64 {
+    let density = if gas { particle_type.gas_density } else { particle_type.liquid_density };
+    let mass = density as f64 * CELL_VOLUME / 1000.0; // g/cm^3 * cm^3 is grams, heat capacity is per kg
+    return (particle_type.heat_capacity as f64 * mass * ENERGY_PER_JOULE as f64).max(f64::MIN_POSITIVE)
+}
 
+impl Phases {
+    // energy is everything it took to warm the cell up from 0 K, each phase at its own mass. so a cell that melts or
+    // boils carries on from the energy it had and just warms faster or slower from there instead of jumping in temperature
+    pub(crate) fn of(particle_type: &ParticleType) -> Phases {
+        let condensed_per_kelvin = heat_per_kelvin(particle_type, false);
+        let melting = particle_type.melting_temperature as f64;
+        let boiling = particle_type.boiling_temperature.max(particle_type.melting_temperature) as f64;
+        return Phases {
+            condensed_per_kelvin,
+            gas_per_kelvin: heat_per_kelvin(particle_type, true),
+            melting,
+            boiling,
+            melt: condensed_per_kelvin * melting,
+            boil: condensed_per_kelvin * boiling,
+            solid: particle_type.solid,
+            liquid_density: particle_type.liquid_density,
+            gas_density: particle_type.gas_density,
+        }
     }
-    /*
-    (Deprecated)
-    pub fn get_color(&self) -> [u8; 3]{
-
-    
-        let mut rgb = self.particle_type.color;
 
-        //return [rgb[0], rgb [1], rgb[2]];
-
-        let mut bb_rgb = [0.0, 0.0, 0.0];
+    pub(crate) fn heat_per_kelvin(&self, state: u8) -> f64 {
+        return if state == 3 { self.gas_per_kelvin } else { self.condensed_per_kelvin }
+    }
 
-// Calculate Blackbody Radiation
-        let temperature = self.get_temperature() as f32 / 100.0;
+    pub(crate) fn temperature(&self, energy: u64) -> f64 {
+        let energy = energy as f64;
+        if energy < self.boil {
+            return energy / self.condensed_per_kelvin
+        }
+        return self.boiling + (energy - self.boil) / self.gas_per_kelvin
+    }
 
-        if temperature <= 66.0{
-            bb_rgb[0] = 255.0;
-            bb_rgb[1] = 99.4708025861 * temperature.ln() - 161.1195681661;
-            bb_rgb[2] = 138.5177312231 * (temperature - 10.0).ln() - 305.0447927307;
+    // the other way around, not rounded
+    pub(crate) fn energy(&self, temperature: f64) -> f64 {
+        if temperature < self.boiling {
+            return temperature.max(0.0) * self.condensed_per_kelvin
         }
-        else {
-            bb_rgb[0] = 329.698727446 * ((temperature - 60.0).powf( -0.1332047592));
-            bb_rgb[1] = 288.1221695283 * ((temperature - 60.0).powf( -0.0755148492));
-            bb_rgb[2] = 255.0;
+        return self.boil + (temperature - self.boiling) * self.gas_per_kelvin
+    }
 
+    // same as comparing the temperature to melting and boiling points, without working the temperature out
+    pub(crate) fn state(&self, energy: u64, crumbled: bool) -> u8 {
+        let energy = energy as f64;
+        if energy < self.melt {
+            if self.solid && !crumbled {
+                return 0
             }
-
-       
-            
-        //println!("{}, {}, {}", bb_rgb[0] as u8, bb_rgb[1] as u8, bb_rgb[2] as u8);
-        //return [bb_rgb[0] as u8, bb_rgb[1] as u8, bb_rgb[2] as u8];
-
-        for i in 0..rgb.len()-1{
-            bb_rgb[i] = bb_rgb[i] * (self.get_temperature() as f32 / 5000.0);   
-
-            let mut color: i32 = rgb [i] as i32;
-            
-            color += self.color_noise as i32 - 128;
-            color += bb_rgb[i] as i32;
-            if color > 255{
-                color = 255;
-            } else if color < 0{
-                color = 0;
+            else {
+                return 1
             }
-            rgb[i] = color as u8;
-        }    
-        return [rgb[0], rgb[1], rgb[2]];
+        }
+        else if energy < self.boil {
+            return 2
+        }
+        return 3
+    }
 
+    pub(crate) fn density(&self, energy: u64, crumbled: bool) -> f32 {
+        let mut density = self.gas_density;
+        if self.state(energy, crumbled) == 2 {
+            density = self.liquid_density;
+        }
+
+        density *= 1.0 + ((self.temperature(energy) as f32) / 500000000.0);
+        return density
     }
-    */
 }
 
+pub(crate) fn in_bounds(width: usize, height: usize, x: usize, y: usize) -> bool {
+    return x < width && y < height
+}
 
 impl ParticleSim{
+    // try_new is the same but checks the size and init_particle's type first
     pub fn new(width: usize, height: usize, init_particle: Particle) -> ParticleSim{
-        return ParticleSim{
-            particles: vec![init_particle; width * height],
+        return ParticleSim::new_seeded(width, height, init_particle, rand::random::<u64>())
+    }
+
+    pub fn new_seeded(width: usize, height: usize, init_particle: Particle, seed: u64) -> ParticleSim{
+        let mut sim = ParticleSim{
+            particles: ParticleStorage::new(width * height, init_particle),
             width,
             height,
+            miscibility: HashMap::new(),
+            ambient_temperature: 293,
+            cooling: HashMap::new(),
+            bodies: HashMap::new(),
+            next_body_id: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            last_tick: None,
+            passes: 0,
+            recording: None,
+            history: None,
+            chunks: ChunkGrid::new(width, height),
+            boundaries: [Boundary::Wall; 4],
+            background: [0, 0, 0, 255],
+            glow: Glow::default(),
+            pass_stamp: 0,
+            #[cfg(feature = "rayon")]
+            parallel: false,
+        };
+        if init_particle.particle_type.texture.is_some() {
+            for y in 0..height {
+                for x in 0..width {
+                    sim.particles.set(x + y * width, init_particle.textured(x as i64, y as i64));
+                }
+            }
         }
+        return sim
     }
 
-    pub fn particle_exists(&self, x: usize, y: usize) -> bool {
-        return x < (self.width - 1) && y < (self.height - 1)
+    pub fn try_new(width: usize, height: usize, init_particle: Particle) -> Result<ParticleSim, SimError> {
+        return ParticleSim::try_new_seeded(width, height, init_particle, rand::random::<u64>())
     }
 
-    pub fn particle_at(&self, x: usize, y: usize) -> &Particle{
-        return &(self.particles[x + y * self.width]);
+    pub fn try_new_seeded(width: usize, height: usize, init_particle: Particle, seed: u64) -> Result<ParticleSim, SimError> {
+        if width == 0 || height == 0 || width.checked_mul(height).is_none() {
+            return Err(SimError::InvalidSize { width, height })
+        }
+        init_particle.particle_type.validate()?;
+        return Ok(ParticleSim::new_seeded(width, height, init_particle, seed))
     }
 
-    pub fn set_particle(&mut self, x: usize, y: usize, particle: Particle){
-        if self.particle_exists(x, y){
-            self.particles[y * self.width + x] = particle;
-        } 
+    // the seed the simulation was created (or last reseeded) with, put this in bug reports
+    pub fn get_seed(&self) -> u64 {
+        return self.seed
     }
 
-    pub fn swap_particles(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
-        if self.particle_exists(x1, y1) && self.particle_exists(x2, y2) {
-            self.particles.swap(x1 + y1 * self.width, x2 + y2 * self.width); 
-        }
+    pub fn reseed(&mut self, seed: u64) {
+        self.record(Edit::Reseed { seed });
+        self.reseed_quietly(seed);
     }
 
-    pub fn get_particle_color(&mut self, x: usize, y: usize) -> [u8; 3]{
-        if self.particle_exists(x, y){
-            return self.particle_at(x, y).get_color();
-        }
-        else {
-            return [0, 0, 0];
-        }
+    // reseed without it going into a recording, for when the recording already knows about it
+    pub(crate) fn reseed_quietly(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
-    pub fn set_particle_energy(&mut self, x: usize, y: usize, energy: u32){
-        if self.particle_exists(x, y){
-            self.particles[y * self.width + x].energy = energy;
-        } 
-
+    pub(crate) fn rng_state(&self) -> StdRng {
+        return self.rng.clone()
     }
 
-    fn set_iterated(&mut self, x: usize, y: usize, iterated_over:bool){
-        self.particles[y * self.width + x].iterated_over = iterated_over;
+    pub(crate) fn set_rng_state(&mut self, rng: StdRng) {
+        self.rng = rng;
     }
 
-    pub fn render_pixels(&mut self) -> Vec<[u8; 3]>{
-        let mut out: Vec<[u8; 3]> = vec![[0, 0, 0]; self.width * self.height];
-        for x in 0..self.width{
-            for y in 0..self.height {
-                out[x + y * self.width] = self.particle_at(x, y).get_color();
-            }
-        }
-        return out
+    // for anything outside the simulation that should stay reproducible too, like textures for spawned particles
+    pub fn rng(&mut self) -> &mut StdRng {
+        return &mut self.rng
     }
 
-    pub fn simulate_sand(&mut self, t: u64){
-        let reverse = t % 2 == 0;
-        for yn in 0..self.height {
-            for xn in 0..self.width {
-                let mut x: i32 = (self.width-xn-1) as i32;
-                let y: i32 = (self.height-yn-1) as i32;
-
-                if reverse{
-                    x = xn as i32
-                }
+    pub fn particle_exists(&self, x: usize, y: usize) -> bool {
+        return in_bounds(self.width, self.height, x, y)
+    }
 
-                if self.particle_exists(x as usize, y as usize){
-                    //println!("1, {}", self.particle_at(x as usize, y as usize).get_state());
-                    let state = self.particle_at(x as usize, y as usize).get_state();
-
-                    if state == 1{
-                        let mut xoffsets = [0, 1, -1];
-                        if rand::random::<bool>(){
-                            xoffsets = [0, -1, 1];
-                        }
-                        let yoffsets = [1, 1, 1];
-                        let mut moved = false;
-                        for i in 0..xoffsets.len(){
-                            let xi = (x + xoffsets[i]) as usize;
-                            let yi = (y + yoffsets[i]) as usize;
-                            if !moved && self.particle_exists(xi, yi){
-                                if 
-                                self.particle_at(x as usize, y as usize).get_density() > self.particle_at(xi, yi).get_density() 
-                                && self.particle_at(xi, yi).get_state()> 1
-                                && !self.particle_at(x as usize, y as usize).iterated_over
-                                {
-                                    if i == 0 || self.particle_at(xi, y as usize).get_state() > 1{
-                                        self.set_iterated(x as usize, y as usize, true);
-                                        moved = true;
-                                        self.swap_particles(x as usize, y as usize, xi, yi)
-                                    }
-                                }
-                            }
-                        }
-                    } 
-                }
-            }
-        }
-        for x in 0..self.width {
-            for y in 0..self.height {
-                 if self.particle_exists(x as usize, y as usize) {
-                     self.set_iterated(x, y, false);
-                 }
-            }
+    // the cell next to (x, y) in the given direction, None past the edge of the grid
+    pub(crate) fn neighbor(&self, x: usize, y: usize, xo: i32, yo: i32) -> Option<(usize, usize)> {
+        let nx = x.checked_add_signed(xo as isize)?;
+        let ny = y.checked_add_signed(yo as isize)?;
+        if !self.particle_exists(nx, ny) {
+            return None
         }
+        return Some((nx, ny))
     }
 
+    // panics outside the grid, get() is the checked version
+    pub fn particle_at(&self, x: usize, y: usize) -> Particle{
+        return self.particles.get(x + y * self.width);
+    }
 
+    // does nothing outside the grid, try_set() is the checked version
+    pub fn set_particle(&mut self, x: usize, y: usize, particle: Particle){
+        self.record(Edit::SetParticle { x, y, particle });
+        self.place_particle(x, y, particle);
+    }
 
-    pub fn simulate_liquids(&mut self, t: u64) {
-
-        let reverse = t % 2 == 0;
-        for yn in 0..self.height {
-            for xn in 0..self.width {
-//                let x = xn as i32;
-//                let y = yn as i32;
-                let mut x: i32 = (self.width-xn-1) as i32;
-                let y: i32 = (self.height-yn-1) as i32;
+    fn place_particle(&mut self, x: usize, y: usize, particle: Particle){
+        self.store_particle(x, y, particle.textured(x as i64, y as i64));
+    }
 
-                if reverse{
-                    x = xn as i32
-                }
+    // set_particle without the texture, for a particle that's already in the grid and only had something else changed
+    pub(crate) fn write_particle(&mut self, x: usize, y: usize, particle: Particle){
+        self.record(Edit::WriteParticle { x, y, particle });
+        self.store_particle(x, y, particle);
+    }
 
-                if self.particle_exists(x as usize, y as usize){
-                    //println!("1, {}", self.particle_at(x as usize, y as usize).get_state());
-                    let state = self.particle_at(x as usize, y as usize).get_state();
-                    
-                    if state == 2 {
-                        let mut xoffsets = [0, 1, -1, 1, -1];
-                        if rand::random::<bool>(){
-                            xoffsets = [0, -1, 1, -1, 1];
-                        }
-                        let yoffsets = [1, 1, 1, 0, 0];
-                        let mut moved = false;
-                        
-                        let mut highest_desity_delta = 0.0;
-                        let mut highest_desity_index: usize = 0;
-                        for i in 0..xoffsets.len(){
-                            let xi = (x + xoffsets[i]) as usize;
-                            let yi = (y + yoffsets[i]) as usize;
-                            if !moved && self.particle_exists(xi, yi){
-                                if 
-                                self.particle_at(x as usize, y as usize).get_density() > self.particle_at(xi, yi).get_density() 
-                                && self.particle_at(xi, yi).get_state() > 1
-                                && !self.particle_at(x as usize, y as usize).iterated_over
-                                {
-                                    if i == 0 {
-                                        self.set_iterated(x as usize, y as usize, true);
-                                        moved = true;
-                                        self.swap_particles(x as usize, y as usize, xi, yi)
-        
-                                    } else if self.particle_at(xi, y as usize).get_state() > 1 
-                                      &&  highest_desity_delta < self.particle_at(xi, yi).get_density(){
-                                        highest_desity_delta = self.particle_at(xi, yi).get_density();
-                                        highest_desity_index = i;
-                                    }
-                                }
-                            }
-                        }
-                        if !moved && highest_desity_delta > 0.0{
-//                            println!("hello {} {}", highest_desity_index, highest_desity_delta);
-                            self.set_iterated(x as usize, y as usize, true);
-                            self.swap_particles(x as usize, y as usize, (x + xoffsets[highest_desity_index]) as usize, (y + yoffsets[highest_desity_index]) as usize);
-                            //self.particle_at(x as usize, y as usize).set_temperature(2000);
-                        }
+    fn store_particle(&mut self, x: usize, y: usize, particle: Particle){
+        if self.particle_exists(x, y){
+            self.particles.set(y * self.width + x, particle);
+            self.wake(x, y);
+        }
+    }
 
+    // fills a circle with copies of the particle, this is what a brush stroke in an editor should go through
+    pub fn paint(&mut self, x: usize, y: usize, radius: usize, particle: Particle){
+        self.record(Edit::Paint { x, y, radius, particle });
+        let r = radius as i64;
+        for yo in -r..=r {
+            for xo in -r..=r {
+                if xo * xo + yo * yo <= r * r {
+                    let px = x as i64 + xo;
+                    let py = y as i64 + yo;
+                    if px >= 0 && py >= 0 {
+                        self.place_particle(px as usize, py as usize, particle);
                     }
-
                 }
-
             }
         }
+    }
 
-        for x in 0..self.width {
-            for y in 0..self.height {
-                 if self.particle_exists(x as usize, y as usize) {
-                     self.set_iterated(x, y, false);
-                 }
-            }
+    pub fn swap_particles(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
+        self.record(Edit::SwapParticles { x1, y1, x2, y2 });
+        if self.particle_exists(x1, y1) && self.particle_exists(x2, y2) {
+            self.particles.swap(x1 + y1 * self.width, x2 + y2 * self.width); 
+            self.wake(x1, y1);
+            self.wake(x2, y2);
         }
     }
 
+    // over the background and with the glow, like render_pixels draws it
+    pub fn get_particle_color(&mut self, x: usize, y: usize) -> [u8; 3]{
+        if self.particle_exists(x, y){
+            let [r, g, b, _] = self.particle_at(x, y).get_color_over(self.background, &self.glow);
+            return [r, g, b];
+        }
+        else {
+            return [0, 0, 0];
+        }
+    }
 
+    pub fn set_particle_energy(&mut self, x: usize, y: usize, energy: u64){
+        self.record(Edit::SetParticleEnergy { x, y, energy });
+        self.write_energy(x, y, energy);
+    }
 
-    pub fn simulate_gasses(&mut self, t: u64) {
-
-        let reverse = t % 2 == 0;
-        for yn in 0..self.height {
-            for xn in 0..self.width {
-                let mut x = xn as i32;
-                let y = yn as i32;
+    fn write_energy(&mut self, x: usize, y: usize, energy: u64){
+        if self.particle_exists(x, y) && self.particles.energies()[y * self.width + x] != energy {
+            self.particles.set_energy(y * self.width + x, energy);
+            self.wake(x, y);
+        }
+    }
 
-                if reverse{
-                    x = (self.width-xn-1) as i32;
-                }
-//                let x: i32 = (self.width-xn-1) as i32;
-//                let y: i32 = (self.height-yn-1) as i32;
-
-                if self.particle_exists(x as usize, y as usize){
-                    //println!("1, {}", self.particle_at(x as usize, y as usize).get_state());
-                    let state = self.particle_at(x as usize, y as usize).get_state();
-                    
-                    if state == 3 {
-                        let mut xoffsets = [0, 1, -1, 1, -1];
-                        if rand::random::<bool>(){
-                            xoffsets = [0, -1, 1, -1, 1];
-                        }
-                        let yoffsets = [-1, -1, -1, 0, 0];
-                        let mut moved = false;
-                        
-                        let mut highest_desity_delta = 0.0;
-                        let mut highest_desity_index: usize = 0;
-                        for i in 0..xoffsets.len(){
-                            let xi = (x + xoffsets[i]) as usize;
-                            let yi = (y + yoffsets[i]) as usize;
-                            if !moved && self.particle_exists(xi, yi){
-                                if 
-                                self.particle_at(x as usize, y as usize).get_density() > self.particle_at(xi, yi).get_density() 
-                                && self.particle_at(xi, yi).get_state() > 1
-                                && !self.particle_at(x as usize, y as usize).iterated_over
-                                {
-                                    if i == 0 {
-                                        self.set_iterated(x as usize, y as usize, true);
-                                        moved = true;
-                                        self.swap_particles(x as usize, y as usize, xi, yi)
-        
-                                    } else if self.particle_at(xi, y as usize).get_state() > 1 
-                                      &&  highest_desity_delta < self.particle_at(xi, yi).get_density(){
-                                        highest_desity_delta = self.particle_at(xi, yi).get_density();
-                                        highest_desity_index = i;
-                                    }
-                                }
-                            }
-                        }
-                        if !moved && highest_desity_delta > 0.0{
-//                            println!("hello {} {}", highest_desity_index, highest_desity_delta);
-                            self.set_iterated(x as usize, y as usize, true);
-                            self.swap_particles(x as usize, y as usize, (x + xoffsets[highest_desity_index]) as usize, (y + yoffsets[highest_desity_index]) as usize);
-                            //self.particle_at(x as usize, y as usize).set_temperature(2000);
-                        }
+    // every pass starts with this, it keeps track of which tick it is for recordings and the chunk timers
+    pub(crate) fn begin_pass(&mut self, t: u64) {
+        self.count_pass(t);
+        // 0 is what fresh particles start with, so skip it when wrapping around
+        self.pass_stamp = self.pass_stamp.wrapping_add(1).max(1);
+        self.chunks.begin_tick(t);
+    }
 
-                    }
+    // the passes that don't go through the chunks only need this part of begin_pass
+    pub(crate) fn count_pass(&mut self, t: u64) {
+        self.last_tick = Some(t);
+        self.passes += 1;
+    }
 
-                }
+    // every simulate_ call counts as one pass, this is what replays line their edits up against
+    pub fn passes(&self) -> u64 {
+        return self.passes
+    }
 
-            }
+    // rate is the chance per tick that two touching liquids of these types swap places, 0.0 makes them immiscible (the default)
+    pub fn set_miscibility(&mut self, a: &ParticleType, b: &ParticleType, rate: f32) {
+        let rate = if rate > 0.0 { rate.min(1.0) } else { 0.0 };
+        self.record(Edit::SetMiscibility { a: *a, b: *b, rate });
+        let key = (a.id.min(b.id), a.id.max(b.id));
+        if rate > 0.0 {
+            self.miscibility.insert(key, rate);
+        } else {
+            self.miscibility.remove(&key);
         }
+    }
 
-        for x in 0..self.width {
-            for y in 0..self.height {
-                 if self.particle_exists(x as usize, y as usize) {
-                     self.set_iterated(x, y, false);
-                 }
-            }
+    pub fn get_miscibility(&self, a: &ParticleType, b: &ParticleType) -> f32 {
+        if a.id == b.id {
+            return 1.0
         }
+        return *self.miscibility.get(&(a.id.min(b.id), a.id.max(b.id))).unwrap_or(&0.0)
+    }
+
+    // render_pixels_with has the debug views
+    pub fn render_pixels(&mut self) -> Vec<[u8; 3]>{
+        return self.render_pixels_with(&RenderMode::Material)
+    }
+
+    // sand, liquids and gasses all in one go. cheaper than running the three passes one after another
+    pub fn simulate_movement(&mut self, t: u64){
+        self.begin_pass(t);
+        self.run_chunked(Pass::Movement, t);
+    }
+
+    pub fn simulate_sand(&mut self, t: u64){
+        self.begin_pass(t);
+        self.run_chunked(Pass::Sand, t);
+    }
+
+    pub fn simulate_liquids(&mut self, t: u64) {
+        self.begin_pass(t);
+        self.run_chunked(Pass::Liquids, t);
+    }
+
+    pub fn simulate_gasses(&mut self, t: u64) {
+        self.begin_pass(t);
+        self.run_chunked(Pass::Gasses, t);
     }
 
     // this is a mess (but it's my mess)
     pub fn simulate_heat(&mut self, t: u64){
-       for x in 0..self.width{
-            for y in 0..self.height{
-                if self.particle_exists(x, y) {
-                    let xoffsets = [-1, -1, -1, 0, 0, 1, 1, 1];
-                    let yoffsets = [-1, 0, 1, -1, 1, -1, 0, 1];
-                    let mut energy_moved: i32 = 0;
-
-                    let particle = *self.particle_at(x, y);
-
-                    for i in 0..xoffsets.len(){
-                        let xo = x as i32 + xoffsets[i];
-                        let yo = y as i32 + yoffsets[i];
-                        if self.particle_exists(xo as usize, yo as usize){
-                            let neighbor_particle = self.particle_at(xo as usize, yo as usize);
-                            let temperature_delta: i32 = particle.get_temperature() as i32 - neighbor_particle.get_temperature() as i32;
-                   
-                            energy_moved += temperature_delta as i32 * particle.particle_type.heat_capacity as i32 / (8 * (particle.particle_type.heat_resistance as i32 + neighbor_particle.particle_type.heat_resistance as i32 + 1));
-                            let np_energy = neighbor_particle.energy as i32 + temperature_delta * particle.particle_type.heat_capacity as i32 / (8 * (particle.particle_type.heat_resistance as i32 + neighbor_particle.particle_type.heat_resistance as i32 + 1));
- 
-                            self.set_particle_energy(xo as usize, yo as usize, np_energy as u32);
-  
-                        }
-                    }
-                    let particle_energy = particle.energy as i32 - energy_moved;
-                    self.set_particle_energy(x, y, particle_energy as u32);
-                }
-            }
-        }
+        self.begin_pass(t);
+        self.run_chunked(Pass::Heat, t);
     }
 
     // you'd think this would be better
     pub fn simulate_heat_simplified(&mut self, t: u64){
-       for x in 0..self.width{
-            for y in 0..self.height{
-                if self.particle_exists(x, y) {
-                    let particle = *self.particle_at(x, y);
-
-                    if particle.get_state() != 3{
-                        let xoffsets = [-1, -1, -1, 0, 0, 1, 1, 1];
-                        let yoffsets = [-1, 0, 1, -1, 1, -1, 0, 1];
-                        let mut energy_moved: i32 = 0;
-
-                        for i in 0..xoffsets.len(){
-                            let xo = x as i32 + xoffsets[i];
-                            let yo = y as i32 + yoffsets[i];
-                            if self.particle_exists(xo as usize, yo as usize){
-                                let neighbor_particle = self.particle_at(xo as usize, yo as usize);
-                                let temperature_delta: i32 = particle.get_temperature() as i32 - neighbor_particle.get_temperature() as i32;
-
-                                if neighbor_particle.get_state() != 3{
-                                    energy_moved += temperature_delta as i32 * particle.particle_type.heat_capacity as i32 / (8 * (particle.particle_type.heat_resistance as i32 + neighbor_particle.particle_type.heat_resistance as i32 + 1));
-                                    let np_energy = neighbor_particle.energy as i32 + temperature_delta * particle.particle_type.heat_capacity as i32 / (8 * (particle.particle_type.heat_resistance as i32 + neighbor_particle.particle_type.heat_resistance as i32 + 1));
-                                    self.set_particle_energy(xo as usize, yo as usize, np_energy as u32);
-                                } else{
-                                    energy_moved += temperature_delta as i32 * particle.particle_type.heat_capacity as i32 / (8 * (particle.particle_type.heat_resistance as i32 + neighbor_particle.particle_type.heat_resistance as i32 + 1));
-                                }
-                                
-                            }
-                        }
-                        let particle_energy = particle.energy as i32 - energy_moved;
-                        self.set_particle_energy(x, y, particle_energy as u32);
-                    }
-                }
-            }
-        }
+        self.begin_pass(t);
+        self.run_chunked(Pass::HeatSimplified, t);
     }
 }
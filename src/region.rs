@@ -0,0 +1,136 @@
+use crate::chunks::ChunkGrid;
+use crate::error::SimError;
+use crate::particle_sim::{Particle, ParticleSim};
+use crate::replay::Edit;
+use crate::storage::ParticleStorage;
+
+// which part of the grid stays put when it's resized, the rest grows or gets cropped around it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    pub(crate) const ALL: [Anchor; 9] = [
+        Anchor::TopLeft, Anchor::Top, Anchor::TopRight,
+        Anchor::Left, Anchor::Center, Anchor::Right,
+        Anchor::BottomLeft, Anchor::Bottom, Anchor::BottomRight,
+    ];
+
+    // where the old top left corner ends up in the resized grid, negative when that side gets cropped
+    fn offset(&self, old_width: usize, old_height: usize, width: usize, height: usize) -> (i64, i64) {
+        let index = Anchor::ALL.iter().position(|anchor| anchor == self).unwrap();
+        let (dw, dh) = (width as i64 - old_width as i64, height as i64 - old_height as i64);
+        let x = match index % 3 { 0 => 0, 1 => dw / 2, _ => dw };
+        let y = match index / 3 { 0 => 0, 1 => dh / 2, _ => dh };
+        return (x, y)
+    }
+}
+
+impl ParticleSim {
+    // changes the size of the grid. new space is filled with `fill`, whatever falls off the edge is cropped.
+    // rigid bodies that get cut are broken up, the rest move along with the particles
+    pub fn resize(&mut self, width: usize, height: usize, anchor: Anchor, fill: Particle) -> Result<(), SimError> {
+        if width == 0 || height == 0 || width.checked_mul(height).is_none() {
+            return Err(SimError::InvalidSize { width, height })
+        }
+        self.record(Edit::Resize { width, height, anchor, fill });
+        let (ox, oy) = anchor.offset(self.width, self.height, width, height);
+        let inside = |x: usize, y: usize| -> bool {
+            let (nx, ny) = (x as i64 + ox, y as i64 + oy);
+            return nx >= 0 && ny >= 0 && nx < width as i64 && ny < height as i64
+        };
+
+        let mut ids: Vec<u32> = self.bodies.keys().copied().collect();
+        ids.sort();
+        for id in ids {
+            if self.bodies[&id].cells.iter().any(|(x, y)| !inside(*x, *y)) {
+                self.let_go_of_body(id);
+            }
+        }
+
+        let mut particles = ParticleStorage::new(width * height, fill);
+        if fill.particle_type.texture.is_some() {
+            for y in 0..height {
+                for x in 0..width {
+                    particles.set(x + y * width, fill.textured(x as i64, y as i64));
+                }
+            }
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if inside(x, y) {
+                    let (nx, ny) = ((x as i64 + ox) as usize, (y as i64 + oy) as usize);
+                    particles.set(nx + ny * width, self.particles.get(x + y * self.width));
+                }
+            }
+        }
+        for body in self.bodies.values_mut() {
+            body.x += ox as f32;
+            body.y += oy as f32;
+            for (x, y) in &mut body.cells {
+                *x = (*x as i64 + ox) as usize;
+                *y = (*y as i64 + oy) as usize;
+            }
+        }
+        self.particles = particles;
+        self.width = width;
+        self.height = height;
+        self.chunks = ChunkGrid::new(width, height);
+        return Ok(())
+    }
+
+    // copies a rectangle out into a simulation of its own. a rectangle hanging off the grid is clipped to it, one that
+    // starts off the grid or has nothing in it is an error. rigid bodies don't come along, their particles are plain
+    // solids in the copy
+    pub fn sub_region(&self, x: usize, y: usize, width: usize, height: usize) -> Result<ParticleSim, SimError> {
+        if width == 0 || height == 0 {
+            return Err(SimError::InvalidSize { width, height })
+        }
+        if x >= self.width || y >= self.height {
+            return Err(SimError::OutOfBounds { x: x as i64, y: y as i64, width: self.width, height: self.height })
+        }
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let mut region = ParticleSim::new_seeded(width, height, self.particle_at(x, y), self.seed);
+        region.miscibility = self.miscibility.clone();
+        region.ambient_temperature = self.ambient_temperature;
+        region.cooling = self.cooling.clone();
+        for ry in 0..height {
+            for rx in 0..width {
+                let mut particle = self.particle_at(x + rx, y + ry);
+                particle.body = 0;
+                particle.body_cell = 0;
+                region.particles.set(rx + ry * width, particle);
+            }
+        }
+        return Ok(region)
+    }
+
+    // writes a region (from sub_region or anywhere else) into the grid with its top left corner at (x, y).
+    // anything sticking out past the edge is left out
+    pub fn paste_region(&mut self, region: &ParticleSim, x: usize, y: usize) {
+        self.record(Edit::PasteRegion { x, y, region: Box::new(region.clone()) });
+        for ry in 0..region.height {
+            for rx in 0..region.width {
+                let (Some(px), Some(py)) = (x.checked_add(rx), y.checked_add(ry)) else { continue };
+                if px < self.width && py < self.height {
+                    let mut particle = region.particle_at(rx, ry);
+                    particle.body = 0;
+                    particle.body_cell = 0;
+                    particle.moved_stamp = 0;
+                    self.particles.set(px + py * self.width, particle);
+                    self.wake(px, py);
+                }
+            }
+        }
+    }
+}
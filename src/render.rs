@@ -0,0 +1,190 @@
+use crate::blackbody::Glow;
+use crate::chunks::CHUNK_SIZE;
+use crate::error::SimError;
+use crate::particle_sim::ParticleSim;
+
+// colours spread evenly from the low end of a range to the high end, blended in between
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRamp {
+    pub stops: Vec<[u8; 3]>,
+}
+
+impl ColorRamp {
+    pub fn new(stops: Vec<[u8; 3]>) -> ColorRamp {
+        return ColorRamp { stops }
+    }
+
+    // t is 0.0 for the first stop and 1.0 for the last, anything outside that gets the colour at the end it's past
+    pub fn sample(&self, t: f32) -> [u8; 3] {
+        if self.stops.is_empty() {
+            return [0, 0, 0]
+        }
+        let position = t.clamp(0.0, 1.0) * (self.stops.len() - 1) as f32;
+        if position.is_nan() {
+            return self.stops[0]
+        }
+        let (i, blend) = (position as usize, position.fract());
+        let (a, b) = (self.stops[i], self.stops[(i + 1).min(self.stops.len() - 1)]);
+        return [0, 1, 2].map(|c| (a[c] as f32 + (b[c] as f32 - a[c] as f32) * blend).round() as u8)
+    }
+}
+
+impl Default for ColorRamp {
+    // black through blue, red and yellow to white, like a thermal camera
+    fn default() -> ColorRamp {
+        return ColorRamp::new(vec![[0, 0, 0], [30, 0, 140], [200, 0, 60], [255, 140, 0], [255, 240, 60], [255, 255, 255]])
+    }
+}
+
+// what render_pixels_with draws. everything but Material is for looking into what the simulation is doing
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderMode {
+    Material, // the particles' own colours and glow over the background, same as render_pixels
+    Heatmap { min_temperature: f32, max_temperature: f32, ramp: ColorRamp }, // in kelvin, colder or hotter clamps to the ends
+    Phase, // grey solids, tan powders, blue liquids and pale gasses
+    Density, // greyscale on a log scale from 0.0001 (black) to 100 g/cm^3 (white), so gasses still show up
+    ActiveChunks, // the materials, dimmed in chunks that are asleep, with the chunk borders marked
+    Velocity { max_speed: f32 }, // how far each particle moved in its last movement pass, black is still and white is max_speed cells or more
+}
+
+// byte order of the pixels render_into writes, 4 bytes each
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8,
+    Bgra8, // what a lot of windowing libraries and textures want
+}
+
+// the part of the grid to draw, in cells. it can hang over the edge of the grid (or be off it entirely), those pixels
+// come out transparent black. every cell is drawn as a scale x scale square
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i64,
+    pub y: i64,
+    pub width: usize,
+    pub height: usize,
+    pub scale: usize,
+}
+
+impl Viewport {
+    pub fn whole(sim: &ParticleSim, scale: usize) -> Viewport {
+        return Viewport { x: 0, y: 0, width: sim.width, height: sim.height, scale }
+    }
+
+    // the size of the image it makes, in pixels. a scale of 0 or an image too big to count the bytes of is an error
+    pub fn pixel_size(&self) -> Result<(usize, usize), SimError> {
+        let invalid = SimError::InvalidSize { width: self.width, height: self.height };
+        if self.scale == 0 {
+            return Err(invalid)
+        }
+        let (pixel_width, pixel_height) = match (self.width.checked_mul(self.scale), self.height.checked_mul(self.scale)) {
+            (Some(pixel_width), Some(pixel_height)) => (pixel_width, pixel_height),
+            _ => return Err(invalid),
+        };
+        if pixel_width.checked_mul(pixel_height).and_then(|pixels| pixels.checked_mul(4)).is_none() {
+            return Err(invalid)
+        }
+        return Ok((pixel_width, pixel_height))
+    }
+}
+
+const PHASE_COLORS: [[u8; 3]; 4] = [[128, 128, 128], [200, 170, 110], [40, 90, 220], [210, 225, 235]];
+
+impl ParticleSim {
+    // what gasses, glass and anything else with alpha below 255 in its colour shows through to. opaque black by
+    // default. with a transparent background render_into hands out each particle's own alpha instead, for blending
+    // over a scene of your own
+    pub fn set_background(&mut self, color: [u8; 4]) {
+        self.background = color;
+    }
+
+    pub fn background(&self) -> [u8; 4] {
+        return self.background
+    }
+
+    // how hot things have to get before they glow in the material views, and how fast they brighten
+    pub fn set_glow(&mut self, glow: Glow) {
+        self.glow = glow;
+    }
+
+    pub fn glow(&self) -> Glow {
+        return self.glow
+    }
+
+    // the alpha is dropped, so with a background that isn't opaque this is the colours without their transparency
+    pub fn render_pixels_with(&self, mode: &RenderMode) -> Vec<[u8; 3]> {
+        let mut out: Vec<[u8; 3]> = vec![[0, 0, 0]; self.width * self.height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [r, g, b, _] = self.pixel_color(x, y, mode);
+                out[x + y * self.width] = [r, g, b];
+            }
+        }
+        return out
+    }
+
+    // draws the viewport into buffer with no allocations, rows packed one after another from the top. the buffer needs
+    // to hold at least the viewport's pixel_size() at 4 bytes a pixel, anything past that is left alone
+    pub fn render_into(&self, buffer: &mut [u8], format: PixelFormat, viewport: Viewport, mode: &RenderMode) -> Result<(), SimError> {
+        let (pixel_width, pixel_height) = viewport.pixel_size()?;
+        let (row_len, needed) = (pixel_width * 4, pixel_width * pixel_height * 4);
+        if buffer.len() < needed {
+            return Err(SimError::BufferTooSmall { needed, len: buffer.len() })
+        }
+
+        for vy in 0..viewport.height {
+            let row_start = vy * viewport.scale * row_len;
+            let row = &mut buffer[row_start..row_start + row_len];
+            for vx in 0..viewport.width {
+                let (x, y) = (viewport.x.saturating_add(vx as i64), viewport.y.saturating_add(vy as i64));
+                let pixel = if x >= 0 && y >= 0 && self.particle_exists(x as usize, y as usize) {
+                    let [r, g, b, a] = self.pixel_color(x as usize, y as usize, mode);
+                    match format {
+                        PixelFormat::Rgba8 => [r, g, b, a],
+                        PixelFormat::Bgra8 => [b, g, r, a],
+                    }
+                } else {
+                    [0, 0, 0, 0]
+                };
+                for chunk in row[vx * viewport.scale * 4..(vx + 1) * viewport.scale * 4].chunks_exact_mut(4) {
+                    chunk.copy_from_slice(&pixel);
+                }
+            }
+            // the rest of the scaled up row is the same as the first
+            for copy in 1..viewport.scale {
+                buffer.copy_within(row_start..row_start + row_len, row_start + copy * row_len);
+            }
+        }
+        return Ok(())
+    }
+
+    // only the material views can come out translucent, the debug ones are always opaque
+    pub(crate) fn pixel_color(&self, x: usize, y: usize, mode: &RenderMode) -> [u8; 4] {
+        let i = x + y * self.width;
+        let [r, g, b] = match mode {
+            RenderMode::Material => return self.particles.get(i).get_color_over(self.background, &self.glow),
+            RenderMode::Heatmap { min_temperature, max_temperature, ramp } => {
+                let temperature = self.particles.get_temperature_f64(i) as f32;
+                ramp.sample((temperature - min_temperature) / (max_temperature - min_temperature))
+            }
+            RenderMode::Phase => PHASE_COLORS[self.particles.get_state(i) as usize],
+            RenderMode::Density => {
+                let level = (self.particles.get_density(i).max(f32::MIN_POSITIVE).log10() + 4.0) / 6.0;
+                [(level.clamp(0.0, 1.0) * 255.0) as u8; 3]
+            }
+            RenderMode::ActiveChunks => {
+                let color = self.particles.get(i).get_color_over(self.background, &self.glow);
+                if x % CHUNK_SIZE == 0 || y % CHUNK_SIZE == 0 {
+                    return [255, 0, 255, 255]
+                } else if self.is_chunk_active(x / CHUNK_SIZE, y / CHUNK_SIZE) {
+                    return color
+                }
+                return [color[0] / 3, color[1] / 3, color[2] / 3, color[3]]
+            }
+            RenderMode::Velocity { max_speed } => {
+                let [vx, vy] = self.particles.velocities()[i].map(f32::from);
+                [(((vx * vx + vy * vy).sqrt() / max_speed).clamp(0.0, 1.0) * 255.0) as u8; 3]
+            }
+        };
+        return [r, g, b, 255]
+    }
+}
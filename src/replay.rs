@@ -0,0 +1,377 @@
+use std::io::{Read, Write};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::boundary::{Boundary, Edge};
+use crate::error::SimError;
+use crate::particle_sim::{Particle, ParticleSim, ParticleType};
+use crate::region::Anchor;
+use crate::serialize::{invalid_data, MAX_CELLS, read_boundary, read_f32, read_particle, read_particle_type, check_particle_type, read_u32, read_u64, read_u8, write_boundary, write_f32, write_particle, write_particle_type, write_u32, write_u64, write_u8};
+
+const REPLAY_MAGIC: &[u8; 4] = b"PREP";
+const REPLAY_VERSION: u32 = 6;
+
+// everything that changes a simulation from the outside. each of these goes through the same method as the original
+// call, and anything that can't be replayed like that (restoring a snapshot, say) carries the state it ended up in
+#[derive(Debug, Clone)]
+pub enum Edit {
+    SetParticle { x: usize, y: usize, particle: Particle },
+    SetParticleEnergy { x: usize, y: usize, energy: u64 },
+    Paint { x: usize, y: usize, radius: usize, particle: Particle },
+    Resize { width: usize, height: usize, anchor: Anchor, fill: Particle },
+    PasteRegion { x: usize, y: usize, region: Box<ParticleSim> },
+    SwapParticles { x1: usize, y1: usize, x2: usize, y2: usize },
+    CreateRigidBody { x: usize, y: usize },
+    BreakRigidBody { id: u32 },
+    SetBoundary { edge: Edge, boundary: Boundary },
+    SetMiscibility { a: ParticleType, b: ParticleType, rate: f32 },
+    SetAmbientTemperature { temperature: u32 },
+    SetCoolingRate { particle_type: ParticleType, rate: f32 },
+    Reseed { seed: u64 },
+    Restore { state: Box<ParticleSim> }, // reseeded from itself right after, like start_recording does
+    WriteParticle { x: usize, y: usize, particle: Particle }, // a change through get_mut, which keeps color_noise as is
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayEvent {
+    // how many passes the simulation had run when the edit was made, see ParticleSim::passes. a tick that runs
+    // several passes can have edits between them, so this goes by pass rather than by tick
+    pub after_pass: u64,
+    pub edit: Edit,
+}
+
+// the state the recording started from plus every edit made since. playing it back while running the same
+// passes with the same ticks gives the exact same simulation
+#[derive(Debug, Clone)]
+pub struct Replay {
+    start_state: Vec<u8>,
+    pub events: Vec<ReplayEvent>,
+}
+
+pub struct ReplayPlayer {
+    replay: Replay,
+    next_event: usize,
+}
+
+impl Edit {
+    // only a resize can fail, and only if the replay was put together by hand
+    pub fn apply(&self, sim: &mut ParticleSim) -> Result<(), SimError> {
+        match self {
+            Edit::SetParticle { x, y, particle } => sim.set_particle(*x, *y, *particle),
+            Edit::SetParticleEnergy { x, y, energy } => sim.set_particle_energy(*x, *y, *energy),
+            Edit::Paint { x, y, radius, particle } => sim.paint(*x, *y, *radius, *particle),
+            Edit::Resize { width, height, anchor, fill } => sim.resize(*width, *height, *anchor, *fill)?,
+            Edit::PasteRegion { x, y, region } => sim.paste_region(region, *x, *y),
+            Edit::SwapParticles { x1, y1, x2, y2 } => sim.swap_particles(*x1, *y1, *x2, *y2),
+            Edit::CreateRigidBody { x, y } => {
+                sim.create_rigid_body(*x, *y);
+            }
+            Edit::BreakRigidBody { id } => sim.break_rigid_body(*id),
+            Edit::SetBoundary { edge, boundary } => sim.set_boundary(*edge, *boundary),
+            Edit::SetMiscibility { a, b, rate } => sim.set_miscibility(a, b, *rate),
+            Edit::SetAmbientTemperature { temperature } => sim.set_ambient_temperature(*temperature),
+            Edit::SetCoolingRate { particle_type, rate } => sim.set_cooling_rate(particle_type, *rate),
+            Edit::Reseed { seed } => sim.reseed(*seed),
+            Edit::WriteParticle { x, y, particle } => sim.write_particle(*x, *y, *particle),
+            Edit::Restore { state } => {
+                sim.record(Edit::Restore { state: state.clone() });
+                sim.load_state((**state).clone());
+                sim.set_rng_state(StdRng::seed_from_u64(state.seed));
+            }
+        }
+        return Ok(())
+    }
+
+    fn write_to(&self, w: &mut impl Write) -> Result<(), SimError> {
+        match self {
+            Edit::SetParticle { x, y, particle } => {
+                write_u8(w, 0)?;
+                write_u32(w, *x as u32)?;
+                write_u32(w, *y as u32)?;
+                write_particle(w, particle)?;
+            }
+            Edit::SetParticleEnergy { x, y, energy } => {
+                write_u8(w, 1)?;
+                write_u32(w, *x as u32)?;
+                write_u32(w, *y as u32)?;
+                write_u64(w, *energy)?;
+            }
+            Edit::Paint { x, y, radius, particle } => {
+                write_u8(w, 2)?;
+                write_u32(w, *x as u32)?;
+                write_u32(w, *y as u32)?;
+                write_u32(w, *radius as u32)?;
+                write_particle(w, particle)?;
+            }
+            Edit::Resize { width, height, anchor, fill } => {
+                write_u8(w, 3)?;
+                write_u32(w, *width as u32)?;
+                write_u32(w, *height as u32)?;
+                write_u8(w, Anchor::ALL.iter().position(|a| a == anchor).unwrap() as u8)?;
+                write_particle(w, fill)?;
+            }
+            Edit::PasteRegion { x, y, region } => {
+                write_u8(w, 4)?;
+                write_u32(w, *x as u32)?;
+                write_u32(w, *y as u32)?;
+                write_sim(w, region)?;
+            }
+            Edit::SwapParticles { x1, y1, x2, y2 } => {
+                write_u8(w, 5)?;
+                for coordinate in [x1, y1, x2, y2] {
+                    write_u32(w, *coordinate as u32)?;
+                }
+            }
+            Edit::CreateRigidBody { x, y } => {
+                write_u8(w, 6)?;
+                write_u32(w, *x as u32)?;
+                write_u32(w, *y as u32)?;
+            }
+            Edit::BreakRigidBody { id } => {
+                write_u8(w, 7)?;
+                write_u32(w, *id)?;
+            }
+            Edit::SetBoundary { edge, boundary } => {
+                write_u8(w, 8)?;
+                write_u8(w, *edge as u8)?;
+                write_boundary(w, boundary)?;
+            }
+            Edit::SetMiscibility { a, b, rate } => {
+                write_u8(w, 9)?;
+                write_particle_type(w, a)?;
+                write_particle_type(w, b)?;
+                write_f32(w, *rate)?;
+            }
+            Edit::SetAmbientTemperature { temperature } => {
+                write_u8(w, 10)?;
+                write_u32(w, *temperature)?;
+            }
+            Edit::SetCoolingRate { particle_type, rate } => {
+                write_u8(w, 11)?;
+                write_particle_type(w, particle_type)?;
+                write_f32(w, *rate)?;
+            }
+            Edit::Reseed { seed } => {
+                write_u8(w, 12)?;
+                write_u64(w, *seed)?;
+            }
+            Edit::Restore { state } => {
+                write_u8(w, 13)?;
+                write_sim(w, state)?;
+            }
+            Edit::WriteParticle { x, y, particle } => {
+                write_u8(w, 14)?;
+                write_u32(w, *x as u32)?;
+                write_u32(w, *y as u32)?;
+                write_particle(w, particle)?;
+            }
+        }
+        return Ok(())
+    }
+
+    fn read_from(r: &mut impl Read) -> Result<Edit, SimError> {
+        let edit = Edit::read_unchecked(r)?;
+        edit.check()?;
+        return Ok(edit)
+    }
+
+    // the materials and rates in an edit, checked like a save checks its own. the regions and restored states in
+    // there went through ParticleSim::read_from already
+    fn check(&self) -> Result<(), SimError> {
+        match self {
+            Edit::SetParticle { particle, .. } | Edit::Paint { particle, .. } | Edit::WriteParticle { particle, .. } | Edit::Resize { fill: particle, .. } => {
+                check_particle_type(&particle.particle_type)?;
+            }
+            Edit::SetBoundary { boundary, .. } => {
+                if let Some(particle) = boundary.particle() {
+                    check_particle_type(&particle.particle_type)?;
+                }
+            }
+            Edit::SetMiscibility { a, b, rate } => {
+                check_particle_type(a)?;
+                check_particle_type(b)?;
+                check_rate(*rate)?;
+            }
+            Edit::SetCoolingRate { particle_type, rate } => {
+                check_particle_type(particle_type)?;
+                check_rate(*rate)?;
+            }
+            _ => {}
+        }
+        return Ok(())
+    }
+
+    fn read_unchecked(r: &mut impl Read) -> Result<Edit, SimError> {
+        return match read_u8(r)? {
+            0 => Ok(Edit::SetParticle { x: read_usize(r)?, y: read_usize(r)?, particle: read_particle(r)? }),
+            1 => Ok(Edit::SetParticleEnergy { x: read_usize(r)?, y: read_usize(r)?, energy: read_u64(r)? }),
+            2 => Ok(Edit::Paint { x: read_usize(r)?, y: read_usize(r)?, radius: read_usize(r)?, particle: read_particle(r)? }),
+            3 => {
+                let (width, height) = (read_usize(r)?, read_usize(r)?);
+                if !matches!(width.checked_mul(height), Some(cells) if cells <= MAX_CELLS) {
+                    return Err(invalid_data("replay resizes to more than a simulation can hold"))
+                }
+                let anchor = *Anchor::ALL.get(read_u8(r)? as usize).ok_or_else(|| invalid_data("unknown anchor in replay"))?;
+                Ok(Edit::Resize { width, height, anchor, fill: read_particle(r)? })
+            }
+            4 => Ok(Edit::PasteRegion { x: read_usize(r)?, y: read_usize(r)?, region: Box::new(read_sim(r)?) }),
+            5 => Ok(Edit::SwapParticles { x1: read_usize(r)?, y1: read_usize(r)?, x2: read_usize(r)?, y2: read_usize(r)? }),
+            6 => Ok(Edit::CreateRigidBody { x: read_usize(r)?, y: read_usize(r)? }),
+            7 => Ok(Edit::BreakRigidBody { id: read_u32(r)? }),
+            8 => {
+                let edge = *Edge::ALL.get(read_u8(r)? as usize).ok_or_else(|| invalid_data("unknown edge in replay"))?;
+                Ok(Edit::SetBoundary { edge, boundary: read_boundary(r)? })
+            }
+            9 => Ok(Edit::SetMiscibility { a: read_particle_type(r)?, b: read_particle_type(r)?, rate: read_f32(r)? }),
+            10 => Ok(Edit::SetAmbientTemperature { temperature: read_u32(r)? }),
+            11 => Ok(Edit::SetCoolingRate { particle_type: read_particle_type(r)?, rate: read_f32(r)? }),
+            12 => Ok(Edit::Reseed { seed: read_u64(r)? }),
+            13 => Ok(Edit::Restore { state: Box::new(read_sim(r)?) }),
+            14 => Ok(Edit::WriteParticle { x: read_usize(r)?, y: read_usize(r)?, particle: read_particle(r)? }),
+            _ => Err(invalid_data("unknown edit in replay")),
+        }
+    }
+}
+
+// the setters record the rate after clamping it, 0.0 being the one that turns it off
+fn check_rate(rate: f32) -> Result<(), SimError> {
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(invalid_data("rate out of range in replay"))
+    }
+    return Ok(())
+}
+
+// coordinates and sizes are saved as u32
+fn read_usize(r: &mut impl Read) -> Result<usize, SimError> {
+    return Ok(read_u32(r)? as usize)
+}
+
+// a whole simulation inside a replay, with its length in front so it's clear where it ends
+fn write_sim(w: &mut impl Write, sim: &ParticleSim) -> Result<(), SimError> {
+    let mut state = Vec::new();
+    sim.write_to(&mut state)?;
+    write_u64(w, state.len() as u64)?;
+    w.write_all(&state)?;
+    return Ok(())
+}
+
+fn read_sim(r: &mut impl Read) -> Result<ParticleSim, SimError> {
+    return ParticleSim::read_from(&mut read_blob(r)?.as_slice())
+}
+
+// a length prefixed run of bytes. the length is only trusted as far as the bytes are actually there, so a broken
+// one can't ask for more memory than the replay itself takes up
+fn read_blob(r: &mut impl Read) -> Result<Vec<u8>, SimError> {
+    let len = read_u64(r)?;
+    let mut blob = Vec::new();
+    r.take(len).read_to_end(&mut blob)?;
+    if blob.len() as u64 != len {
+        return Err(invalid_data("replay ends in the middle of a simulation"))
+    }
+    return Ok(blob)
+}
+
+impl Replay {
+    // the first tick to simulate when playing this back
+    pub fn first_tick(&self) -> u64 {
+        return match self.start().last_tick {
+            Some(tick) => tick + 1,
+            None => 0,
+        }
+    }
+
+    // a fresh copy of the simulation as it was when the recording started
+    pub fn start(&self) -> ParticleSim {
+        return ParticleSim::read_unchecked(&mut self.start_state.as_slice()).expect("replay start state is always valid")
+    }
+
+    pub fn play(self) -> (ParticleSim, ReplayPlayer) {
+        return (self.start(), ReplayPlayer { replay: self, next_event: 0 })
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> Result<(), SimError> {
+        w.write_all(REPLAY_MAGIC)?;
+        write_u32(w, REPLAY_VERSION)?;
+        write_u64(w, self.start_state.len() as u64)?;
+        w.write_all(&self.start_state)?;
+        write_u64(w, self.events.len() as u64)?;
+        for event in &self.events {
+            write_u64(w, event.after_pass)?;
+            event.edit.write_to(w)?;
+        }
+        return Ok(())
+    }
+
+    pub fn read_from(r: &mut impl Read) -> Result<Replay, SimError> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC {
+            return Err(invalid_data("not a replay"))
+        }
+        if read_u32(r)? != REPLAY_VERSION {
+            return Err(invalid_data("unsupported replay version"))
+        }
+        let start_state = read_blob(r)?;
+        // make sure the start state actually loads now rather than when someone hits play
+        ParticleSim::read_from(&mut start_state.as_slice())?;
+
+        let count = read_u64(r)?;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let after_pass = read_u64(r)?;
+            events.push(ReplayEvent { after_pass, edit: Edit::read_from(r)? });
+        }
+        return Ok(Replay { start_state, events })
+    }
+}
+
+impl ReplayPlayer {
+    // call this before every pass (and once more after the last one), it applies every edit that was made before
+    // the simulation got this far. an edit that fails is skipped and the rest still go in, so the replay stays on
+    // schedule. the first error comes back afterwards for whoever's playing it back to decide what to do with
+    pub fn apply_edits(&mut self, sim: &mut ParticleSim) -> Result<(), SimError> {
+        let mut result = Ok(());
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.after_pass > sim.passes() {
+                break
+            }
+            self.next_event += 1;
+            if let Err(error) = event.edit.apply(sim) {
+                result = result.and(Err(error));
+            }
+        }
+        return result
+    }
+
+    pub fn finished(&self) -> bool {
+        return self.next_event >= self.replay.events.len()
+    }
+}
+
+impl ParticleSim {
+    // starts recording edits into a replay. this reseeds the random generator from itself so the recording
+    // knows exactly where it starts without needing to save the generator's internals
+    pub fn start_recording(&mut self) {
+        let seed = self.rng().gen::<u64>();
+        self.reseed_quietly(seed);
+        let mut start_state = Vec::new();
+        self.write_to(&mut start_state).expect("writing to a vec can't fail");
+        self.recording = Some(Replay { start_state, events: Vec::new() });
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        return self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        return self.recording.is_some()
+    }
+
+    pub(crate) fn record(&mut self, edit: Edit) {
+        let after_pass = self.passes;
+        if let Some(recording) = &mut self.recording {
+            recording.events.push(ReplayEvent { after_pass, edit });
+        }
+    }
+}
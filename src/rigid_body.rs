@@ -0,0 +1,494 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::particle_sim::{Particle, ParticleSim};
+use crate::replay::Edit;
+
+const GRAVITY: f32 = 0.2; // cells per tick^2
+const MAX_SPEED: f32 = 8.0; // cells per tick
+const TIP_ACCELERATION: f32 = 0.01; // radians per tick^2 when a body hangs over an edge
+const NEIGHBOR_OFFSETS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// a group of connected solid particles that moves as one. the particles themselves stay in the grid,
+// the body just remembers where each of them sits relative to its centre so it can move and rotate them together
+#[derive(Debug, Clone)]
+pub struct RigidBody {
+    pub id: u32,
+    pub x: f32, // centre of the body in grid coordinates
+    pub y: f32,
+    pub velocity: [f32; 2],
+    pub angle: f32, // radians, positive is clockwise on screen (y points down)
+    pub angular_velocity: f32,
+    pub(crate) shape: Vec<(f32, f32)>, // offset of every cell from the centre at angle 0, indexed by Particle::body_cell
+    pub(crate) cells: Vec<(usize, usize)>, // where the cells were last placed in the grid
+}
+
+impl RigidBody {
+    // where every cell of the shape ends up with the body at the given pose, before snapping to the grid
+    fn transform(&self, x: f32, y: f32, angle: f32) -> Vec<(f32, f32)> {
+        let (sin, cos) = angle.sin_cos();
+        return self.shape.iter().map(|(ox, oy)| (x + ox * cos - oy * sin, y + ox * sin + oy * cos)).collect()
+    }
+
+    fn radius(&self) -> f32 {
+        return self.shape.iter().fold(1.0, |r, (ox, oy)| r.max((ox * ox + oy * oy).sqrt()))
+    }
+}
+
+impl Particle {
+    // id of the rigid body this particle belongs to, 0 if it isn't part of one
+    pub fn get_body(&self) -> u32 {
+        return self.body
+    }
+}
+
+impl ParticleSim {
+    pub fn rigid_bodies(&self) -> impl Iterator<Item = &RigidBody> {
+        return self.bodies.values()
+    }
+
+    pub fn rigid_body(&self, id: u32) -> Option<&RigidBody> {
+        return self.bodies.get(&id)
+    }
+
+    // turns every solid particle connected to (x, y) into a single rigid body, returns its id
+    pub fn create_rigid_body(&mut self, x: usize, y: usize) -> Option<u32> {
+        if !self.particle_exists(x, y) || self.particle_at(x, y).get_state() != 0 || self.particle_at(x, y).body != 0 {
+            return None
+        }
+        self.record(Edit::CreateRigidBody { x, y });
+        let cells = self.flood_fill_solid(x, y, |p| p.body == 0);
+        return Some(self.add_body(cells, [0.0, 0.0], 0.0))
+    }
+
+    // lets go of every particle in the body, they stay where they are as plain static solids
+    pub fn break_rigid_body(&mut self, id: u32) {
+        self.record(Edit::BreakRigidBody { id });
+        self.let_go_of_body(id);
+    }
+
+    // break_rigid_body without recording it, for edits that break bodies as part of something that's recorded itself
+    pub(crate) fn let_go_of_body(&mut self, id: u32) {
+        if let Some(body) = self.bodies.remove(&id) {
+            for (x, y) in body.cells {
+                if self.particle_at(x, y).body == id {
+                    self.set_body(x, y, 0, 0);
+                }
+            }
+        }
+    }
+
+    // a cell's body isn't anything the passes look at, so this doesn't wake the chunk up. the history still needs to know
+    pub(crate) fn set_body(&mut self, x: usize, y: usize, body: u32, body_cell: u32) {
+        self.particles.set_body(x + y * self.width, body, body_cell);
+        self.chunks.touch(x, y);
+    }
+
+    pub(crate) fn flood_fill_solid(&self, x: usize, y: usize, include: impl Fn(&Particle) -> bool) -> Vec<(usize, usize)> {
+        let mut cells = vec![(x, y)];
+        let mut seen = HashSet::from([(x, y)]);
+        let mut i = 0;
+        while i < cells.len() {
+            let (cx, cy) = cells[i];
+            for (xo, yo) in NEIGHBOR_OFFSETS {
+                let Some((nx, ny)) = self.neighbor(cx, cy, xo, yo) else { continue };
+                if !seen.contains(&(nx, ny)) {
+                    let neighbor = self.particle_at(nx, ny);
+                    if neighbor.get_state() == 0 && include(&neighbor) {
+                        seen.insert((nx, ny));
+                        cells.push((nx, ny));
+                    }
+                }
+            }
+            i += 1;
+        }
+        return cells
+    }
+
+    pub(crate) fn add_body(&mut self, cells: Vec<(usize, usize)>, velocity: [f32; 2], angular_velocity: f32) -> u32 {
+        self.next_body_id += 1;
+        let id = self.next_body_id;
+
+        let count = cells.len() as f32;
+        let cx = cells.iter().map(|(x, _)| *x as f32).sum::<f32>() / count;
+        let cy = cells.iter().map(|(_, y)| *y as f32).sum::<f32>() / count;
+        // snap the centre to the grid so the shape rasterizes back onto the exact same cells at angle 0
+        let (cx, cy) = (cx.round(), cy.round());
+
+        let mut shape = Vec::with_capacity(cells.len());
+        for (i, (x, y)) in cells.iter().enumerate() {
+            shape.push((*x as f32 - cx, *y as f32 - cy));
+            self.set_body(*x, *y, id, i as u32);
+        }
+
+        self.bodies.insert(id, RigidBody {
+            id,
+            x: cx,
+            y: cy,
+            velocity,
+            angle: 0.0,
+            angular_velocity,
+            shape,
+            cells,
+        });
+        return id
+    }
+
+    // groups any rigid-type solids that aren't part of a body yet into new bodies. also forgets the body of any particle
+    // that got separated from it, like a melted cell that has flowed away since
+    fn build_rigid_bodies(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.particle_exists(x, y) {
+                    let particle = self.particle_at(x, y);
+                    if particle.body != 0 {
+                        let placed = self.bodies.get(&particle.body).and_then(|b| b.cells.get(particle.body_cell as usize));
+                        if placed != Some(&(x, y)) {
+                            self.set_body(x, y, 0, 0);
+                        }
+                    }
+                    let particle = self.particle_at(x, y);
+                    if particle.particle_type.rigid && particle.body == 0 && particle.get_state() == 0 {
+                        let cells = self.flood_fill_solid(x, y, |p| p.particle_type.rigid && p.body == 0);
+                        self.add_body(cells, [0.0, 0.0], 0.0);
+                    }
+                }
+            }
+        }
+    }
+
+    // drops cells that melted or got replaced since the last tick, and splits the body up if that broke it in pieces
+    fn refresh_body(&mut self, id: u32) {
+        let body = self.bodies.get(&id).unwrap();
+        let mut alive = Vec::with_capacity(body.cells.len());
+        for (i, (x, y)) in body.cells.iter().enumerate() {
+            let particle = self.particle_at(*x, *y);
+            if particle.body == id && particle.body_cell == i as u32 {
+                if particle.get_state() == 0 {
+                    alive.push((*x, *y));
+                } else {
+                    // the body is still borrowed here, so this is set_body by hand
+                    self.particles.set_body(x + y * self.width, 0, 0);
+                    self.chunks.touch(*x, *y);
+                }
+            }
+        }
+        if alive.len() == body.cells.len() {
+            return
+        }
+
+        let velocity = body.velocity;
+        let angular_velocity = body.angular_velocity;
+        self.bodies.remove(&id);
+        for (x, y) in &alive {
+            self.set_body(*x, *y, 0, 0);
+        }
+        // whatever is left might be in several pieces now
+        self.add_pieces(alive, velocity, angular_velocity);
+    }
+
+    // makes a body out of every connected piece of the given cells, none of which can be in a body already
+    fn add_pieces(&mut self, cells: Vec<(usize, usize)>, velocity: [f32; 2], angular_velocity: f32) {
+        let cells: HashSet<(usize, usize)> = cells.into_iter().collect();
+        let mut assigned: HashSet<(usize, usize)> = HashSet::new();
+        let mut starts: Vec<&(usize, usize)> = cells.iter().collect();
+        starts.sort();
+        for start in starts {
+            if assigned.contains(start) {
+                continue
+            }
+            let piece = connected_within(&cells, *start);
+            assigned.extend(piece.iter().copied());
+            self.add_body(piece, velocity, angular_velocity);
+        }
+    }
+
+    // how fast a body has to hit something to crack, in cells per tick. it's the weakest material's strength shared
+    // out over the body's cells, so a body as big as its strength allows cracks landing at 1 cell per tick and one
+    // half that size takes twice the speed
+    fn breaking_speed(&self, id: u32) -> f32 {
+        let body = &self.bodies[&id];
+        let strength = body.cells.iter().map(|(x, y)| self.particle_at(*x, *y).particle_type.strength).min().unwrap_or(0);
+        return strength as f32 / body.cells.len() as f32
+    }
+
+    // a hard landing cracks a body in two across its longer side, each half carries on as a body of its own
+    fn crack_body(&mut self, id: u32) {
+        let body = self.bodies.remove(&id).unwrap();
+        for (x, y) in &body.cells {
+            self.set_body(*x, *y, 0, 0);
+        }
+        let mut cells = body.cells;
+        let width = cells.iter().map(|(x, _)| *x).max().unwrap() - cells.iter().map(|(x, _)| *x).min().unwrap();
+        let height = cells.iter().map(|(_, y)| *y).max().unwrap() - cells.iter().map(|(_, y)| *y).min().unwrap();
+        if width >= height {
+            cells.sort_by_key(|(x, y)| (*x, *y));
+        } else {
+            cells.sort_by_key(|(x, y)| (*y, *x));
+        }
+        let second = cells.split_off(cells.len() / 2);
+        self.add_pieces(cells, body.velocity, 0.0);
+        self.add_pieces(second, body.velocity, 0.0);
+    }
+
+    fn body_density(&self, body: &RigidBody) -> f32 {
+        let total: f32 = body.cells.iter().map(|(x, y)| self.particle_at(*x, *y).get_density()).sum();
+        return total / body.cells.len() as f32
+    }
+
+    // a body can push aside liquids and gasses that are lighter than it, everything else stops it
+    fn body_can_occupy(&self, id: u32, density: f32, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || !self.particle_exists(x as usize, y as usize) {
+            return false
+        }
+        let particle = self.particle_at(x as usize, y as usize);
+        if particle.body == id {
+            return true
+        }
+        return particle.body == 0 && particle.get_state() > 1 && particle.get_density() < density
+    }
+
+    // tries to put the body at the given pose, moving whatever it displaces into the cells it leaves behind
+    fn try_place_body(&mut self, id: u32, density: f32, x: f32, y: f32, angle: f32) -> bool {
+        let body = self.bodies.get(&id).unwrap();
+
+        // rotated shapes don't land on the grid one to one, so a cell that rounds onto an already claimed spot
+        // takes the closest free one around it instead. that way the body never gains or loses particles
+        let mut new = Vec::with_capacity(body.shape.len());
+        let mut new_set = HashSet::with_capacity(body.shape.len());
+        for (tx, ty) in body.transform(x, y, angle) {
+            // only the spot the cell rounds to counts for collisions, the others are just there to fix up rounding
+            if !self.body_can_occupy(id, density, tx.round() as i32, ty.round() as i32) {
+                return false
+            }
+            let mut candidates: Vec<(i32, i32)> = Vec::with_capacity(9);
+            for xo in -1..=1 {
+                for yo in -1..=1 {
+                    candidates.push((tx.round() as i32 + xo, ty.round() as i32 + yo));
+                }
+            }
+            candidates.sort_by(|a, b| {
+                let da = (a.0 as f32 - tx).powi(2) + (a.1 as f32 - ty).powi(2);
+                let db = (b.0 as f32 - tx).powi(2) + (b.1 as f32 - ty).powi(2);
+                da.total_cmp(&db)
+            });
+            let spot = candidates.into_iter().find(|(cx, cy)| {
+                self.body_can_occupy(id, density, *cx, *cy) && !new_set.contains(&(*cx as usize, *cy as usize))
+            });
+            match spot {
+                Some((cx, cy)) => {
+                    new.push((cx as usize, cy as usize));
+                    new_set.insert((cx as usize, cy as usize));
+                }
+                None => return false,
+            }
+        }
+        let old: HashSet<(usize, usize)> = body.cells.iter().copied().collect();
+
+        let mut freed: Vec<(usize, usize)> = old.iter().filter(|c| !new_set.contains(c)).copied().collect();
+        let mut displaced: Vec<(usize, usize)> = new.iter().filter(|c| !old.contains(c)).copied().collect();
+        freed.sort_by_key(|(x, y)| (*y, *x));
+        displaced.sort_by_key(|(x, y)| (*y, *x));
+
+        let body_particles: Vec<Particle> = body.cells.iter().map(|(cx, cy)| self.particle_at(*cx, *cy)).collect();
+        let displaced_particles: Vec<Particle> = displaced.iter().map(|(dx, dy)| self.particle_at(*dx, *dy)).collect();
+
+        for ((fx, fy), particle) in freed.iter().zip(displaced_particles) {
+            self.particles.set(fx + fy * self.width, particle);
+            self.wake(*fx, *fy);
+        }
+        for ((nx, ny), particle) in new.iter().zip(body_particles) {
+            self.particles.set(nx + ny * self.width, particle);
+            self.wake(*nx, *ny);
+        }
+
+        let body = self.bodies.get_mut(&id).unwrap();
+        body.x = x;
+        body.y = y;
+        body.angle = angle;
+        body.cells = new;
+        return true
+    }
+
+    // cells the body is resting on, used to figure out if it should tip over an edge
+    fn support_range(&self, id: u32, density: f32) -> Option<(f32, f32)> {
+        let body = self.bodies.get(&id).unwrap();
+        let mut range: Option<(f32, f32)> = None;
+        for (x, y) in &body.cells {
+            if !self.body_can_occupy(id, density, *x as i32, *y as i32 + 1) {
+                let x = *x as f32;
+                range = Some(match range {
+                    Some((min, max)) => (min.min(x), max.max(x)),
+                    None => (x, x),
+                });
+            }
+        }
+        return range
+    }
+
+    // moves every body under gravity, floating, sinking and tipping over edges. a body that lands at its breaking
+    // speed or faster cracks in two, see breaking_speed
+    pub fn simulate_rigid_bodies(&mut self, t: u64) {
+        self.count_pass(t);
+        let mut ids: Vec<u32> = self.bodies.keys().copied().collect();
+        ids.sort();
+        for id in ids {
+            self.refresh_body(id);
+        }
+        self.build_rigid_bodies();
+
+        let mut ids: Vec<u32> = self.bodies.keys().copied().collect();
+        ids.sort();
+        // bodies lower down move first so a stack doesn't get stuck on itself
+        ids.sort_by(|a, b| self.bodies[b].y.total_cmp(&self.bodies[a].y));
+
+        for id in ids {
+            let density = {
+                let body = &self.bodies[&id];
+                self.body_density(body)
+            };
+
+            let body = self.bodies.get_mut(&id).unwrap();
+            body.velocity[1] = (body.velocity[1] + GRAVITY).min(MAX_SPEED);
+            let radius = body.radius();
+            let [vx, vy] = body.velocity;
+            let angular_velocity = body.angular_velocity;
+
+            // move in steps of at most one cell (and at most one cell of rotation at the edge) so nothing gets tunnelled through
+            let steps = vx.abs().max(vy.abs()).max(angular_velocity.abs() * radius).ceil().max(1.0) as usize;
+            let (dx, dy, da) = (vx / steps as f32, vy / steps as f32, angular_velocity / steps as f32);
+
+            let mut blocked_x = false;
+            let mut blocked_y = false;
+            let mut blocked_angle = false;
+            for _ in 0..steps {
+                let body = &self.bodies[&id];
+                let (x, y, angle) = (body.x, body.y, body.angle);
+                if !blocked_angle && da != 0.0 && !self.try_place_body(id, density, x, y, angle + da) {
+                    blocked_angle = true;
+                }
+                let angle = self.bodies[&id].angle;
+                // bodies lighter than the liquid under them still sink in until they displace their own weight
+                let push_density = if dy > 0.0 && self.sinks_further(id, density) { f32::MAX } else { density };
+                if !blocked_y && dy != 0.0 && !self.try_place_body(id, push_density, x, y + dy, angle) {
+                    blocked_y = true;
+                }
+                let y = self.bodies[&id].y;
+                if !blocked_x && dx != 0.0 && !self.try_place_body(id, density, x + dx, y, angle) {
+                    blocked_x = true;
+                }
+            }
+
+            let cx = self.bodies[&id].x;
+            let falling_blocked = blocked_y && self.bodies[&id].velocity[1] > 0.0;
+
+            // lighter than the liquid it's sitting in, float up instead
+            let rise = falling_blocked && self.body_is_submerged(id, density) && {
+                let body = &self.bodies[&id];
+                let (x, y, angle) = (body.x, body.y, body.angle);
+                self.try_place_body(id, f32::MAX, x, y - 1.0, angle)
+            };
+
+            let support = if falling_blocked && !rise { self.support_range(id, density) } else { None };
+            // a body that's only resting gets gravity's worth of speed every tick, that's not an impact
+            let impact = self.bodies[&id].velocity[1];
+            let cracks = falling_blocked && !rise && impact > GRAVITY && impact >= self.breaking_speed(id) && self.bodies[&id].cells.len() > 1;
+
+            let body = self.bodies.get_mut(&id).unwrap();
+            if blocked_x {
+                body.velocity[0] = 0.0;
+            }
+            if blocked_y || rise {
+                body.velocity[1] = 0.0;
+                body.velocity[0] *= 0.8; // friction
+            }
+            if blocked_angle {
+                body.angular_velocity = 0.0;
+            }
+            match support {
+                Some((min, _)) if cx < min - 0.5 => body.angular_velocity -= TIP_ACCELERATION,
+                Some((_, max)) if cx > max + 0.5 => body.angular_velocity += TIP_ACCELERATION,
+                Some(_) => body.angular_velocity = 0.0,
+                None => {}
+            }
+            if cracks {
+                self.crack_body(id);
+            }
+        }
+    }
+
+    // rough archimedes: a row of the body counts as under water when the liquid on either side of it is denser than the body,
+    // and the body keeps sinking until the share of its cells in those rows matches how much lighter than the liquid it is
+    fn sinks_further(&self, id: u32, density: f32) -> bool {
+        let body = &self.bodies[&id];
+        let mut rows: HashMap<usize, (usize, usize, usize)> = HashMap::new(); // y -> (leftmost x, rightmost x, cell count)
+        for (x, y) in &body.cells {
+            let row = rows.entry(*y).or_insert((*x, *x, 0));
+            row.0 = row.0.min(*x);
+            row.1 = row.1.max(*x);
+            row.2 += 1;
+        }
+
+        let mut submerged = 0;
+        let mut liquid_density: f32 = 0.0;
+        for (y, (left, right, count)) in &rows {
+            for x in [*left as i32 - 1, *right as i32 + 1] {
+                if x >= 0 && self.particle_exists(x as usize, *y) {
+                    let side = self.particle_at(x as usize, *y);
+                    if side.body == 0 && side.get_state() == 2 && side.get_density() > density {
+                        submerged += count;
+                        liquid_density = liquid_density.max(side.get_density());
+                        break
+                    }
+                }
+            }
+        }
+        for (x, y) in &body.cells {
+            if self.particle_exists(*x, y + 1) {
+                let below = self.particle_at(*x, y + 1);
+                if below.body == 0 && below.get_state() == 2 {
+                    liquid_density = liquid_density.max(below.get_density());
+                }
+            }
+        }
+        if liquid_density <= density {
+            return false
+        }
+        return (submerged as f32 / body.cells.len() as f32) < density / liquid_density
+    }
+
+    // every cell right above the body is a liquid denser than the body, so it should bob up
+    fn body_is_submerged(&self, id: u32, density: f32) -> bool {
+        let body = &self.bodies[&id];
+        let mut top: HashMap<usize, usize> = HashMap::new();
+        for (x, y) in &body.cells {
+            let entry = top.entry(*x).or_insert(*y);
+            *entry = (*entry).min(*y);
+        }
+        return top.iter().all(|(x, y)| {
+            *y > 0 && {
+                let above = self.particle_at(*x, y - 1);
+                above.body == 0 && above.get_state() == 2 && above.get_density() > density
+            }
+        })
+    }
+}
+
+fn connected_within(allowed: &HashSet<(usize, usize)>, start: (usize, usize)) -> Vec<(usize, usize)> {
+    let mut cells = vec![start];
+    let mut seen = HashSet::from([start]);
+    let mut i = 0;
+    while i < cells.len() {
+        let (cx, cy) = cells[i];
+        for (xo, yo) in NEIGHBOR_OFFSETS {
+            let next = match (cx.checked_add_signed(xo as isize), cy.checked_add_signed(yo as isize)) {
+                (Some(nx), Some(ny)) => (nx, ny),
+                _ => continue,
+            };
+            if allowed.contains(&next) && seen.insert(next) {
+                cells.push(next);
+            }
+        }
+        i += 1;
+    }
+    return cells
+}
@@ -0,0 +1,478 @@
+use std::io::{self, Read, Write};
+
+use half::f16;
+
+use crate::boundary::Boundary;
+use crate::error::SimError;
+use crate::particle_sim::{Particle, ParticleSim, ParticleType};
+use crate::rigid_body::RigidBody;
+use crate::texture::{Pattern, Texture};
+
+// everything is little endian. particle types are written once into a palette and particles refer to them by index
+const STATE_MAGIC: &[u8; 4] = b"PSIM";
+const STATE_VERSION: u32 = 8;
+// a file can claim any size it likes and the grid is allocated before the cells are read, so anything bigger than
+// this (16384x16384) is taken to be broken rather than something to try allocating
+pub(crate) const MAX_CELLS: usize = 1 << 28;
+
+pub(crate) fn write_u8(w: &mut impl Write, value: u8) -> io::Result<()> {
+    return w.write_all(&[value])
+}
+
+pub(crate) fn write_bool(w: &mut impl Write, value: bool) -> io::Result<()> {
+    return write_u8(w, value as u8)
+}
+
+pub(crate) fn write_u16(w: &mut impl Write, value: u16) -> io::Result<()> {
+    return w.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u32(w: &mut impl Write, value: u32) -> io::Result<()> {
+    return w.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u64(w: &mut impl Write, value: u64) -> io::Result<()> {
+    return w.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_f32(w: &mut impl Write, value: f32) -> io::Result<()> {
+    return w.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    return Ok(buf[0])
+}
+
+pub(crate) fn read_bool(r: &mut impl Read) -> io::Result<bool> {
+    return Ok(read_u8(r)? != 0)
+}
+
+pub(crate) fn read_u16(r: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    return Ok(u16::from_le_bytes(buf))
+}
+
+pub(crate) fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    return Ok(u32::from_le_bytes(buf))
+}
+
+pub(crate) fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    return Ok(u64::from_le_bytes(buf))
+}
+
+pub(crate) fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    return Ok(f32::from_le_bytes(buf))
+}
+
+pub(crate) fn invalid_data(message: &str) -> SimError {
+    return SimError::Format(message.to_string())
+}
+
+pub(crate) fn write_particle_type(w: &mut impl Write, particle_type: &ParticleType) -> io::Result<()> {
+    write_u32(w, particle_type.id)?;
+    w.write_all(&particle_type.vapor_color)?;
+    w.write_all(&particle_type.liquid_color)?;
+    w.write_all(&particle_type.solid_color)?;
+    write_bool(w, particle_type.solid)?;
+    write_f32(w, particle_type.liquid_density)?;
+    write_f32(w, particle_type.gas_density)?;
+    write_u16(w, particle_type.melting_temperature)?;
+    write_u16(w, particle_type.boiling_temperature)?;
+    write_u32(w, particle_type.heat_capacity)?;
+    write_u16(w, particle_type.heat_resistance)?;
+    write_f32(w, particle_type.surface_tension)?;
+    write_bool(w, particle_type.rigid)?;
+    write_bool(w, particle_type.anchored)?;
+    write_u32(w, particle_type.strength)?;
+    return write_texture(w, &particle_type.texture)
+}
+
+pub(crate) fn read_particle_type(r: &mut impl Read) -> Result<ParticleType, SimError> {
+    let id = read_u32(r)?;
+    let mut vapor_color = [0; 4];
+    r.read_exact(&mut vapor_color)?;
+    let mut liquid_color = [0; 4];
+    r.read_exact(&mut liquid_color)?;
+    let mut solid_color = [0; 4];
+    r.read_exact(&mut solid_color)?;
+    return Ok(ParticleType {
+        id,
+        vapor_color,
+        liquid_color,
+        solid_color,
+        solid: read_bool(r)?,
+        liquid_density: read_f32(r)?,
+        gas_density: read_f32(r)?,
+        melting_temperature: read_u16(r)?,
+        boiling_temperature: read_u16(r)?,
+        heat_capacity: read_u32(r)?,
+        heat_resistance: read_u16(r)?,
+        surface_tension: read_f32(r)?,
+        rigid: read_bool(r)?,
+        anchored: read_bool(r)?,
+        strength: read_u32(r)?,
+        texture: read_texture(r)?,
+    })
+}
+
+// a type that ParticleType::validate turns away, as a Format error since it came from a file
+pub(crate) fn check_particle_type(particle_type: &ParticleType) -> Result<(), SimError> {
+    return particle_type.validate().map_err(|error| SimError::Format(format!("bad particle type in file: {}", error)))
+}
+
+// a miscibility or cooling rate. the setters only ever store ones in (0, 1], anything else didn't come from them
+pub(crate) fn read_rate(r: &mut impl Read) -> Result<f32, SimError> {
+    let rate = read_f32(r)?;
+    if !(rate > 0.0 && rate <= 1.0) {
+        return Err(invalid_data("rate out of range"))
+    }
+    return Ok(rate)
+}
+
+// a kind byte (0 for no texture), the pattern's parameters, then strength and seed
+fn write_texture(w: &mut impl Write, texture: &Option<Texture>) -> io::Result<()> {
+    let texture = match texture {
+        Some(texture) => texture,
+        None => return write_u8(w, 0),
+    };
+    match texture.pattern {
+        Pattern::Random => write_u8(w, 1)?,
+        Pattern::Metal { size } => {
+            write_u8(w, 2)?;
+            write_u32(w, size)?;
+        }
+        Pattern::ValueNoise { scale } => {
+            write_u8(w, 3)?;
+            write_f32(w, scale)?;
+        }
+        Pattern::Perlin { scale, octaves } => {
+            write_u8(w, 4)?;
+            write_f32(w, scale)?;
+            write_u8(w, octaves)?;
+        }
+        Pattern::Wood { ring_spacing, grain } => {
+            write_u8(w, 5)?;
+            write_f32(w, ring_spacing)?;
+            write_f32(w, grain)?;
+        }
+        Pattern::Brick { width, height, mortar } => {
+            write_u8(w, 6)?;
+            write_u32(w, width)?;
+            write_u32(w, height)?;
+            write_u32(w, mortar)?;
+        }
+        Pattern::Crystal { size } => {
+            write_u8(w, 7)?;
+            write_f32(w, size)?;
+        }
+        Pattern::Speckle { density } => {
+            write_u8(w, 8)?;
+            write_f32(w, density)?;
+        }
+    }
+    write_u8(w, texture.strength)?;
+    return write_u64(w, texture.seed)
+}
+
+fn read_texture(r: &mut impl Read) -> Result<Option<Texture>, SimError> {
+    let pattern = match read_u8(r)? {
+        0 => return Ok(None),
+        1 => Pattern::Random,
+        2 => Pattern::Metal { size: read_u32(r)? },
+        3 => Pattern::ValueNoise { scale: read_f32(r)? },
+        4 => Pattern::Perlin { scale: read_f32(r)?, octaves: read_u8(r)? },
+        5 => Pattern::Wood { ring_spacing: read_f32(r)?, grain: read_f32(r)? },
+        6 => Pattern::Brick { width: read_u32(r)?, height: read_u32(r)?, mortar: read_u32(r)? },
+        7 => Pattern::Crystal { size: read_f32(r)? },
+        8 => Pattern::Speckle { density: read_f32(r)? },
+        _ => return Err(invalid_data("unknown texture pattern")),
+    };
+    return Ok(Some(Texture { pattern, strength: read_u8(r)?, seed: read_u64(r)? }))
+}
+
+// a particle on its own, type and all. used where there's no palette to point into, like single edits in a replay
+pub(crate) fn write_particle(w: &mut impl Write, particle: &Particle) -> io::Result<()> {
+    write_particle_type(w, &particle.particle_type)?;
+    return write_particle_state(w, particle)
+}
+
+pub(crate) fn read_particle(r: &mut impl Read) -> Result<Particle, SimError> {
+    let particle_type = read_particle_type(r)?;
+    return Ok(read_particle_state(r, particle_type)?)
+}
+
+pub(crate) fn write_particle_state(w: &mut impl Write, particle: &Particle) -> io::Result<()> {
+    write_u64(w, particle.energy)?;
+    write_u8(w, particle.color_noise)?;
+    write_bool(w, particle.crumbled)?;
+    write_u16(w, particle.velocity[0].to_bits())?;
+    write_u16(w, particle.velocity[1].to_bits())?;
+    write_u32(w, particle.body)?;
+    write_u32(w, particle.body_cell)?;
+    return Ok(())
+}
+
+pub(crate) fn read_particle_state(r: &mut impl Read, particle_type: ParticleType) -> io::Result<Particle> {
+    let mut particle = Particle::new(particle_type);
+    particle.energy = read_u64(r)?;
+    particle.color_noise = read_u8(r)?;
+    particle.crumbled = read_bool(r)?;
+    particle.velocity = [f16::from_bits(read_u16(r)?), f16::from_bits(read_u16(r)?)];
+    particle.body = read_u32(r)?;
+    particle.body_cell = read_u32(r)?;
+    return Ok(particle)
+}
+
+fn write_body(w: &mut impl Write, body: &RigidBody) -> io::Result<()> {
+    write_u32(w, body.id)?;
+    write_f32(w, body.x)?;
+    write_f32(w, body.y)?;
+    write_f32(w, body.velocity[0])?;
+    write_f32(w, body.velocity[1])?;
+    write_f32(w, body.angle)?;
+    write_f32(w, body.angular_velocity)?;
+    write_u32(w, body.shape.len() as u32)?;
+    for ((ox, oy), (x, y)) in body.shape.iter().zip(&body.cells) {
+        write_f32(w, *ox)?;
+        write_f32(w, *oy)?;
+        write_u32(w, *x as u32)?;
+        write_u32(w, *y as u32)?;
+    }
+    return Ok(())
+}
+
+fn read_body(r: &mut impl Read) -> io::Result<RigidBody> {
+    let id = read_u32(r)?;
+    let x = read_f32(r)?;
+    let y = read_f32(r)?;
+    let velocity = [read_f32(r)?, read_f32(r)?];
+    let angle = read_f32(r)?;
+    let angular_velocity = read_f32(r)?;
+    let count = read_u32(r)? as usize;
+    // the count comes straight from the file, so the vecs only grow as the cells actually turn up
+    let mut shape = Vec::new();
+    let mut cells = Vec::new();
+    for _ in 0..count {
+        shape.push((read_f32(r)?, read_f32(r)?));
+        cells.push((read_u32(r)? as usize, read_u32(r)? as usize));
+    }
+    return Ok(RigidBody { id, x, y, velocity, angle, angular_velocity, shape, cells })
+}
+
+pub(crate) fn write_boundary(w: &mut impl Write, boundary: &Boundary) -> io::Result<()> {
+    return match boundary {
+        Boundary::Wall => write_u8(w, 0),
+        Boundary::Void(particle) => {
+            write_u8(w, 1)?;
+            write_particle(w, particle)
+        }
+        Boundary::Wrap => write_u8(w, 2),
+        Boundary::Open(particle) => {
+            write_u8(w, 3)?;
+            write_particle(w, particle)
+        }
+    }
+}
+
+pub(crate) fn read_boundary(r: &mut impl Read) -> Result<Boundary, SimError> {
+    return match read_u8(r)? {
+        0 => Ok(Boundary::Wall),
+        1 => Ok(Boundary::Void(read_particle(r)?)),
+        2 => Ok(Boundary::Wrap),
+        3 => Ok(Boundary::Open(read_particle(r)?)),
+        _ => Err(invalid_data("unknown boundary")),
+    }
+}
+
+// what write_header wrote
+pub(crate) struct Header {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub last_tick: Option<u64>,
+    pub passes: u64,
+}
+
+impl Header {
+    pub(crate) fn read_from(r: &mut impl Read) -> Result<Header, SimError> {
+        let width = read_u32(r)? as usize;
+        let height = read_u32(r)? as usize;
+        let seed = read_u64(r)?;
+        let has_tick = read_bool(r)?;
+        let tick = read_u64(r)?;
+        let passes = read_u64(r)?;
+        if !matches!(width.checked_mul(height), Some(cells) if cells <= MAX_CELLS) {
+            return Err(invalid_data("simulation is too big"))
+        }
+        return Ok(Header { width, height, seed, last_tick: if has_tick { Some(tick) } else { None }, passes })
+    }
+
+    // a simulation of this size filled with fill, with the seed and pass counts already in place
+    pub(crate) fn sim(&self, fill: ParticleType) -> ParticleSim {
+        let mut sim = ParticleSim::new_seeded(self.width, self.height, Particle::new(fill), self.seed);
+        sim.last_tick = self.last_tick;
+        sim.passes = self.passes;
+        return sim
+    }
+}
+
+impl ParticleSim {
+    // writes out the whole simulation. the random generator is saved as its seed, so a loaded simulation
+    // only continues the same way as the original if it was reseeded right before saving
+    pub fn write_to(&self, w: &mut impl Write) -> Result<(), SimError> {
+        w.write_all(STATE_MAGIC)?;
+        write_u32(w, STATE_VERSION)?;
+        self.write_header(w)?;
+
+        // the storage's own palette, minus whatever no cell uses any more. types that only differ in something
+        // other than their id are separate entries there, so they stay separate here
+        let types = self.particles.types();
+        let mut palette_index: Vec<Option<u32>> = vec![None; types.len()];
+        let mut palette: Vec<&ParticleType> = Vec::new();
+        for index in self.particles.type_indices() {
+            palette_index[*index as usize].get_or_insert_with(|| {
+                palette.push(&types[*index as usize]);
+                palette.len() as u32 - 1
+            });
+        }
+        write_u32(w, palette.len() as u32)?;
+        for particle_type in palette {
+            write_particle_type(w, particle_type)?;
+        }
+        for (i, index) in self.particles.type_indices().iter().enumerate() {
+            write_u32(w, palette_index[*index as usize].expect("every used type is in the palette"))?;
+            write_particle_state(w, &self.particles.get(i))?;
+        }
+        return self.write_settings(w)
+    }
+
+    // the size, seed and how far it's got, the part of a save that comes before the cells
+    pub(crate) fn write_header(&self, w: &mut impl Write) -> io::Result<()> {
+        write_u32(w, self.width as u32)?;
+        write_u32(w, self.height as u32)?;
+        write_u64(w, self.seed)?;
+        write_bool(w, self.last_tick.is_some())?;
+        write_u64(w, self.last_tick.unwrap_or(0))?;
+        return write_u64(w, self.passes)
+    }
+
+    // everything that comes after the cells
+    pub(crate) fn write_settings(&self, w: &mut impl Write) -> Result<(), SimError> {
+        let mut miscibility: Vec<(&(u32, u32), &f32)> = self.miscibility.iter().collect();
+        miscibility.sort_by_key(|(key, _)| **key);
+        write_u32(w, miscibility.len() as u32)?;
+        for ((a, b), rate) in miscibility {
+            write_u32(w, *a)?;
+            write_u32(w, *b)?;
+            write_f32(w, *rate)?;
+        }
+
+        let mut ids: Vec<&u32> = self.bodies.keys().collect();
+        ids.sort();
+        write_u32(w, self.next_body_id)?;
+        write_u32(w, ids.len() as u32)?;
+        for id in ids {
+            write_body(w, &self.bodies[id])?;
+        }
+
+        for boundary in &self.boundaries {
+            write_boundary(w, boundary)?;
+        }
+
+        write_u32(w, self.ambient_temperature)?;
+        let mut cooling: Vec<(&u32, &f32)> = self.cooling.iter().collect();
+        cooling.sort_by_key(|(id, _)| **id);
+        write_u32(w, cooling.len() as u32)?;
+        for (id, rate) in cooling {
+            write_u32(w, *id)?;
+            write_f32(w, *rate)?;
+        }
+        return Ok(())
+    }
+
+    // a save can hold anything, so every material and rate in it is checked the way the try_ functions and the
+    // setters would, and anything they'd turn away is a Format error
+    pub fn read_from(r: &mut impl Read) -> Result<ParticleSim, SimError> {
+        let sim = ParticleSim::read_unchecked(r)?;
+        for particle_type in sim.particles.types() {
+            check_particle_type(particle_type)?;
+        }
+        for particle in sim.boundaries.iter().filter_map(|boundary| boundary.particle()) {
+            check_particle_type(&particle.particle_type)?;
+        }
+        return Ok(sim)
+    }
+
+    // read_from without checking the materials, for bytes written from a running simulation. the plain functions let
+    // that hold materials that don't validate, and taking a snapshot of it shouldn't mean it can't be restored
+    pub(crate) fn read_unchecked(r: &mut impl Read) -> Result<ParticleSim, SimError> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != STATE_MAGIC {
+            return Err(invalid_data("not a particle simulation"))
+        }
+        if read_u32(r)? != STATE_VERSION {
+            return Err(invalid_data("unsupported simulation version"))
+        }
+        let header = Header::read_from(r)?;
+        let (width, height) = (header.width, header.height);
+
+        let palette_len = read_u32(r)? as usize;
+        if palette_len > u16::MAX as usize + 1 {
+            return Err(invalid_data("more than 65536 particle types"))
+        }
+        let mut palette = Vec::new();
+        for _ in 0..palette_len {
+            palette.push(read_particle_type(r)?);
+        }
+        if palette.is_empty() || width == 0 || height == 0 {
+            return Err(invalid_data("empty simulation"))
+        }
+
+        let mut sim = header.sim(palette[0]);
+        let indices: Vec<u16> = palette.iter().map(|particle_type| sim.particles.palette_index(particle_type)).collect();
+        for i in 0..width * height {
+            let index = read_u32(r)? as usize;
+            let particle_type = *palette.get(index).ok_or_else(|| invalid_data("particle type out of range"))?;
+            sim.particles.set_indexed(i, indices[index], &read_particle_state(r, particle_type)?);
+        }
+        sim.read_settings(r)?;
+        return Ok(sim)
+    }
+
+    pub(crate) fn read_settings(&mut self, r: &mut impl Read) -> Result<(), SimError> {
+        for _ in 0..read_u32(r)? {
+            let key = (read_u32(r)?, read_u32(r)?);
+            self.miscibility.insert(key, read_rate(r)?);
+        }
+
+        self.next_body_id = read_u32(r)?;
+        for _ in 0..read_u32(r)? {
+            let body = read_body(r)?;
+            if body.cells.iter().any(|(x, y)| *x >= self.width || *y >= self.height) {
+                return Err(invalid_data("rigid body cell out of range"))
+            }
+            self.bodies.insert(body.id, body);
+        }
+
+        for boundary in &mut self.boundaries {
+            *boundary = read_boundary(r)?;
+        }
+
+        self.ambient_temperature = read_u32(r)?;
+        for _ in 0..read_u32(r)? {
+            let id = read_u32(r)?;
+            self.cooling.insert(id, read_rate(r)?);
+        }
+        return Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::chunks::CHUNK_SIZE;
+use crate::error::SimError;
+use crate::particle_sim::ParticleSim;
+use crate::replay::Edit;
+use crate::serialize::{read_particle_state, read_particle_type, read_u16, read_u32, write_particle_state, write_particle_type, write_u16, write_u32, Header};
+
+// a full copy of the simulation at one point in time
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    state: Vec<u8>,
+    rng: StdRng,
+}
+
+// the simulation the way the history keeps it: everything but the cells in one piece, then the cells chunk by chunk.
+// a chunk that was asleep the whole time since the last snapshot can't have changed, so only the ones that were
+// awake (or touched some other way) get encoded again
+#[derive(Debug, Clone)]
+struct Encoded {
+    meta: Vec<u8>, // header, the storage's palette as is and the settings
+    chunks: Vec<Vec<u8>>, // every cell as its palette index and state, by chunk index
+    rng: StdRng,
+    size: (usize, usize), // the chunks are cut up differently for a different size
+    palette_id: u64, // the cells' palette indices only mean anything with this palette, see ParticleStorage
+}
+
+// what it takes to get from one snapshot back to the one before it
+#[derive(Debug, Clone)]
+struct Undo {
+    meta: Vec<u8>,
+    rng: StdRng,
+    size: (usize, usize),
+    palette_id: u64,
+    chunk_count: usize,
+    chunks: Vec<(usize, Vec<u8>)>, // the chunks that changed, as they were before
+}
+
+// bounded history of snapshots. only the newest one is kept whole, every older one is stored as what changed
+// compared to the one after it, so a mostly static world costs next to nothing per snapshot
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    capacity: usize,
+    newest: Option<Encoded>,
+    older: VecDeque<Undo>, // front is the oldest
+}
+
+impl ParticleSim {
+    fn encode_meta(&self) -> Vec<u8> {
+        let mut meta = Vec::new();
+        self.write_header(&mut meta).expect("writing to a vec can't fail");
+        let types = self.particles.types();
+        write_u32(&mut meta, types.len() as u32).expect("writing to a vec can't fail");
+        for particle_type in types {
+            write_particle_type(&mut meta, particle_type).expect("writing to a vec can't fail");
+        }
+        self.write_settings(&mut meta).expect("writing to a vec can't fail");
+        return meta
+    }
+
+    fn chunk_cells(&self, chunk: usize) -> impl Iterator<Item = usize> {
+        let (cx, cy) = (chunk % self.chunks.cols, chunk / self.chunks.cols);
+        let (x0, x1) = (cx * CHUNK_SIZE, ((cx + 1) * CHUNK_SIZE).min(self.width));
+        let (y0, y1) = (cy * CHUNK_SIZE, ((cy + 1) * CHUNK_SIZE).min(self.height));
+        let width = self.width;
+        return (y0..y1).flat_map(move |y| (x0..x1).map(move |x| x + y * width))
+    }
+
+    fn encode_chunk(&self, chunk: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for i in self.chunk_cells(chunk) {
+            write_u16(&mut bytes, self.particles.type_indices()[i]).expect("writing to a vec can't fail");
+            write_particle_state(&mut bytes, &self.particles.get(i)).expect("writing to a vec can't fail");
+        }
+        return bytes
+    }
+}
+
+impl Encoded {
+    fn of(sim: &ParticleSim) -> Encoded {
+        let chunks = (0..sim.chunks.cols * sim.chunks.rows).map(|chunk| sim.encode_chunk(chunk)).collect();
+        let (size, palette_id) = ((sim.width, sim.height), sim.particles.palette_id());
+        return Encoded { meta: sim.encode_meta(), chunks, rng: sim.rng_state(), size, palette_id }
+    }
+
+    // brings this up to date with the simulation, returning what it takes to go back
+    fn update(&mut self, sim: &ParticleSim, dirty: &[bool]) -> Undo {
+        let meta = sim.encode_meta();
+        let count = sim.chunks.cols * sim.chunks.rows;
+        // a different palette or a resized grid makes every chunk suspect, the ones that come out the same still
+        // don't cost anything
+        let everything = self.palette_id != sim.particles.palette_id() || self.size != (sim.width, sim.height);
+        let mut undo = Undo {
+            meta: std::mem::replace(&mut self.meta, meta),
+            rng: std::mem::replace(&mut self.rng, sim.rng_state()),
+            size: self.size,
+            palette_id: self.palette_id,
+            chunk_count: self.chunks.len(),
+            chunks: Vec::new(),
+        };
+        (self.size, self.palette_id) = ((sim.width, sim.height), sim.particles.palette_id());
+        for (chunk, dirty) in dirty.iter().enumerate().take(count) {
+            if everything || *dirty {
+                let bytes = sim.encode_chunk(chunk);
+                if chunk >= self.chunks.len() {
+                    self.chunks.push(bytes);
+                } else if self.chunks[chunk] != bytes {
+                    undo.chunks.push((chunk, std::mem::replace(&mut self.chunks[chunk], bytes)));
+                }
+            }
+        }
+        for chunk in count..self.chunks.len() {
+            undo.chunks.push((chunk, std::mem::take(&mut self.chunks[chunk])));
+        }
+        self.chunks.truncate(count);
+        return undo
+    }
+
+    fn undo(&mut self, undo: Undo) {
+        (self.meta, self.rng, self.size, self.palette_id) = (undo.meta, undo.rng, undo.size, undo.palette_id);
+        self.chunks.resize(undo.chunk_count, Vec::new());
+        for (chunk, bytes) in undo.chunks {
+            self.chunks[chunk] = bytes;
+        }
+    }
+
+    // the palette goes back in in the same order, so the indices in the chunks line up again
+    fn decode(&self) -> Result<ParticleSim, SimError> {
+        let r = &mut self.meta.as_slice();
+        let header = Header::read_from(r)?;
+        let mut palette = Vec::new();
+        for _ in 0..read_u32(r)? {
+            palette.push(read_particle_type(r)?);
+        }
+        let mut sim = header.sim(palette[0]);
+        for particle_type in &palette[1..] {
+            sim.particles.palette_index(particle_type);
+        }
+        sim.read_settings(r)?;
+        for (chunk, bytes) in self.chunks.iter().enumerate() {
+            let r = &mut bytes.as_slice();
+            for i in sim.chunk_cells(chunk).collect::<Vec<usize>>() {
+                let index = read_u16(r)?;
+                let particle = read_particle_state(r, palette[index as usize])?;
+                sim.particles.set_indexed(i, index, &particle);
+            }
+        }
+        return Ok(sim)
+    }
+}
+
+impl History {
+    fn new(capacity: usize) -> History {
+        return History { capacity, newest: None, older: VecDeque::new() }
+    }
+
+    fn len(&self) -> usize {
+        return self.older.len() + self.newest.is_some() as usize
+    }
+
+    fn push(&mut self, sim: &ParticleSim, dirty: &[bool]) {
+        match &mut self.newest {
+            Some(newest) => self.older.push_back(newest.update(sim, dirty)),
+            None => self.newest = Some(Encoded::of(sim)),
+        }
+        while self.len() > self.capacity {
+            self.older.pop_front();
+        }
+    }
+
+    // drops the newest n snapshots and returns the simulation and generator that are newest after that
+    fn rewind(&mut self, n: usize) -> Option<(ParticleSim, StdRng)> {
+        if n >= self.len() {
+            return None
+        }
+        let newest = self.newest.as_mut().unwrap();
+        for _ in 0..n {
+            newest.undo(self.older.pop_back().unwrap());
+        }
+        let sim = newest.decode().expect("history entries are always valid");
+        // decode put the palette back in the same order, so the chunks are still good for the new one
+        newest.palette_id = sim.particles.palette_id();
+        return Some((sim, newest.rng.clone()))
+    }
+}
+
+impl ParticleSim {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut state = Vec::new();
+        self.write_to(&mut state).expect("writing to a vec can't fail");
+        return Snapshot { state, rng: self.rng_state() }
+    }
+
+    // puts the simulation back the way it was when the snapshot was taken. the history and any running
+    // recording are left alone. a recording can't hold the random generator's internals, so while recording
+    // it carries on from a seed drawn from the snapshot's generator instead, the same as start_recording does
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        let loaded = ParticleSim::read_unchecked(&mut snapshot.state.as_slice()).expect("snapshots are always valid");
+        self.restore_loaded(loaded, snapshot.rng.clone());
+    }
+
+    fn restore_loaded(&mut self, mut loaded: ParticleSim, mut rng: StdRng) {
+        if self.is_recording() {
+            loaded.seed = rng.gen();
+            rng = StdRng::seed_from_u64(loaded.seed);
+            self.record(Edit::Restore { state: Box::new(loaded.clone()) });
+        }
+        self.load_state(loaded);
+        self.set_rng_state(rng);
+    }
+
+    // everything but the history, the recording, the random generator and how it's drawn, from another simulation
+    pub(crate) fn load_state(&mut self, loaded: ParticleSim) {
+        self.particles = loaded.particles;
+        self.width = loaded.width;
+        self.height = loaded.height;
+        self.miscibility = loaded.miscibility;
+        self.ambient_temperature = loaded.ambient_temperature;
+        self.cooling = loaded.cooling;
+        self.bodies = loaded.bodies;
+        self.next_body_id = loaded.next_body_id;
+        self.seed = loaded.seed;
+        self.last_tick = loaded.last_tick;
+        self.passes = loaded.passes;
+        self.chunks = loaded.chunks;
+        self.boundaries = loaded.boundaries;
+    }
+
+    // how many snapshots the history keeps before dropping the oldest, 0 turns it off
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        if capacity == 0 {
+            self.history = None;
+            return
+        }
+        let history = self.history.get_or_insert_with(|| History::new(capacity));
+        history.capacity = capacity;
+        while history.len() > capacity {
+            history.older.pop_front();
+        }
+    }
+
+    // forgets every snapshot but keeps the history turned on
+    pub(crate) fn clear_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.newest = None;
+            history.older.clear();
+        }
+    }
+
+    pub fn history_len(&self) -> usize {
+        return self.history.as_ref().map_or(0, |history| history.len())
+    }
+
+    // saves the current state into the history, call it once per tick (and after edits you want to be able to undo).
+    // only the chunks that were awake since the last one get looked at, anything that writes to `particles` directly
+    // has to wake_all() for the history to notice
+    pub fn push_snapshot(&mut self) {
+        let Some(mut history) = self.history.take() else { return };
+        let dirty = self.chunks.take_dirty();
+        history.push(self, &dirty);
+        self.history = Some(history);
+    }
+
+    // goes back n snapshots, rewind(0) just returns to the newest one. the snapshots skipped over are thrown away
+    // so the simulation carries on from there. returns false without changing anything if there isn't that much history
+    pub fn rewind(&mut self, n: usize) -> bool {
+        let (loaded, rng) = match self.history.as_mut().and_then(|history| history.rewind(n)) {
+            Some(newest) => newest,
+            None => return false,
+        };
+        let recording = self.is_recording();
+        self.restore_loaded(loaded, rng);
+        // the simulation is exactly the newest snapshot again, so nothing's changed since. unless restoring reseeded
+        if !recording {
+            self.chunks.take_dirty();
+        }
+        return true
+    }
+}
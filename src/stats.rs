@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use crate::particle_sim::ParticleSim;
+
+// everything summed up over a rectangle of the grid. the temperatures are all 0 if the rectangle is empty
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldStats {
+    pub cells: usize,
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    pub mean_temperature: f32,
+    pub total_energy: u128, // in the same units as Particle::energy, a u64 per cell doesn't add up in a u64
+    pub materials: HashMap<u32, usize>, // particle type id to how many there are
+    pub phases: [usize; 4], // indexed by state, so solid, powder, liquid, gas
+}
+
+impl ParticleSim {
+    // the rectangle clipped to the grid, as ranges of x and y
+    fn clip(&self, x: usize, y: usize, width: usize, height: usize) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+        let (x0, y0) = (x.min(self.width), y.min(self.height));
+        return (x0..x.saturating_add(width).min(self.width), y0..y.saturating_add(height).min(self.height))
+    }
+
+    // one value per cell in kelvin, laid out like the grid (x + y * width)
+    pub fn temperature_field(&self) -> Vec<f32> {
+        return self.temperature_field_in(0, 0, self.width, self.height)
+    }
+
+    // clipped to the grid, so the rows can come out narrower than width if it sticks out past the edge
+    pub fn temperature_field_in(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<f32> {
+        let (xs, ys) = self.clip(x, y, width, height);
+        return ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .map(|(x, y)| self.particles.get_temperature_f64(x + y * self.width) as f32)
+            .collect()
+    }
+
+    pub fn density_field(&self) -> Vec<f32> {
+        return self.density_field_in(0, 0, self.width, self.height)
+    }
+
+    pub fn density_field_in(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<f32> {
+        let (xs, ys) = self.clip(x, y, width, height);
+        return ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .map(|(x, y)| self.particles.get_density(x + y * self.width))
+            .collect()
+    }
+
+    pub fn total_energy(&self) -> u128 {
+        return self.particles.energies().iter().map(|energy| *energy as u128).sum()
+    }
+
+    pub fn stats(&self) -> FieldStats {
+        return self.stats_in(0, 0, self.width, self.height)
+    }
+
+    pub fn stats_in(&self, x: usize, y: usize, width: usize, height: usize) -> FieldStats {
+        let (xs, ys) = self.clip(x, y, width, height);
+        let mut stats = FieldStats {
+            cells: 0,
+            min_temperature: f32::MAX,
+            max_temperature: 0.0,
+            mean_temperature: 0.0,
+            total_energy: 0,
+            materials: HashMap::new(),
+            phases: [0; 4],
+        };
+        let mut temperature_sum = 0.0;
+        for y in ys {
+            for x in xs.clone() {
+                let i = x + y * self.width;
+                let temperature = self.particles.get_temperature_f64(i);
+                stats.cells += 1;
+                stats.min_temperature = stats.min_temperature.min(temperature as f32);
+                stats.max_temperature = stats.max_temperature.max(temperature as f32);
+                temperature_sum += temperature;
+                stats.total_energy += self.particles.energies()[i] as u128;
+                *stats.materials.entry(self.particles.particle_type(i).id).or_insert(0) += 1;
+                stats.phases[self.particles.get_state(i) as usize] += 1;
+            }
+        }
+        if stats.cells == 0 {
+            stats.min_temperature = 0.0;
+        } else {
+            stats.mean_temperature = (temperature_sum / stats.cells as f64) as f32;
+        }
+        return stats
+    }
+}
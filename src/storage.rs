@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use half::f16;
+
+use crate::particle_sim::{Particle, ParticleType, Phases};
+use crate::serialize::write_particle_type;
+
+const CRUMBLED: u8 = 1; // bit in flags
+const MIN_COMPACT: usize = 256;
+
+static NEXT_PALETTE_ID: AtomicU64 = AtomicU64::new(0);
+
+// the particles of the grid stored column by column instead of as one big struct per cell. every cell used to carry a
+// full copy of its ParticleType around, now the types live in a palette and a cell only keeps a u16 index into it,
+// so a pass that only looks at energy or type only has to pull those columns through the cache
+#[derive(Debug, Clone)]
+pub struct ParticleStorage {
+    types: Vec<ParticleType>, // the palette, every distinct type put into the grid since the last compact
+    phases: Vec<Phases>, // worked out once for each entry of the palette
+    index: HashMap<Vec<u8>, u16>, // the palette by the type's saved bytes, which unlike == also matches a NaN density
+    last_type: usize, // palette index of the last type that was set, most edits set the same type over and over
+    compact_at: usize, // make_room compacts once the palette grows past this
+    // changes whenever indices that were handed out stop meaning the same type, so anything that kept them knows.
+    // it's unique across storages rather than a count, two storages that both start at 0 would look the same otherwise
+    palette_id: u64,
+    type_index: Vec<u16>,
+    energy: Vec<u64>,
+    color_noise: Vec<u8>,
+    flags: Vec<u8>,
+    velocity: Vec<[f16; 2]>,
+    moved_stamp: Vec<u32>,
+    body: Vec<u32>,
+    body_cell: Vec<u32>,
+}
+
+// raw pointers to every column so the chunks of one checkerboard phase can work on the grid from several threads,
+// see ParticleSim::run_chunked for why that's fine. the palette is only read through this, never grown
+#[derive(Copy, Clone)]
+pub(crate) struct RawStorage {
+    types: *const ParticleType,
+    phases: *const Phases,
+    type_index: *mut u16,
+    energy: *mut u64,
+    color_noise: *mut u8,
+    flags: *mut u8,
+    velocity: *mut [f16; 2],
+    moved_stamp: *mut u32,
+    body: *mut u32,
+    body_cell: *mut u32,
+}
+
+unsafe impl Send for RawStorage {}
+unsafe impl Sync for RawStorage {}
+
+impl ParticleStorage {
+    pub fn new(len: usize, particle: Particle) -> ParticleStorage {
+        let mut storage = ParticleStorage {
+            types: Vec::new(),
+            phases: Vec::new(),
+            index: HashMap::new(),
+            last_type: 0,
+            compact_at: MIN_COMPACT,
+            palette_id: NEXT_PALETTE_ID.fetch_add(1, Ordering::Relaxed),
+            type_index: Vec::new(),
+            energy: Vec::new(),
+            color_noise: Vec::new(),
+            flags: Vec::new(),
+            velocity: Vec::new(),
+            moved_stamp: Vec::new(),
+            body: Vec::new(),
+            body_cell: Vec::new(),
+        };
+        let index = storage.palette_index(&particle.particle_type);
+        storage.type_index = vec![index; len];
+        storage.energy = vec![particle.energy; len];
+        storage.color_noise = vec![particle.color_noise; len];
+        storage.flags = vec![flags_of(&particle); len];
+        storage.velocity = vec![particle.velocity; len];
+        storage.moved_stamp = vec![particle.moved_stamp; len];
+        storage.body = vec![particle.body; len];
+        storage.body_cell = vec![particle.body_cell; len];
+        return storage
+    }
+
+    // the palette index of a type, adding it if it's new. the passes can't grow the palette while they run, so anything
+    // they might write into the grid has to be put in here beforehand, after a make_room for however many there are.
+    // a full palette gets compacted first, which moves the indices of everything else around. it only panics if the
+    // grid really does hold 65536 different types at once
+    pub(crate) fn palette_index(&mut self, particle_type: &ParticleType) -> u16 {
+        if self.types.get(self.last_type) == Some(particle_type) {
+            return self.last_type as u16
+        }
+        let mut key = Vec::new();
+        write_particle_type(&mut key, particle_type).expect("writing to a vec can't fail");
+        if let Some(index) = self.index.get(&key) {
+            self.last_type = *index as usize;
+            return *index
+        }
+        if self.types.len() > u16::MAX as usize {
+            self.compact();
+            assert!(self.types.len() <= u16::MAX as usize, "more than 65536 different particle types in one grid");
+        }
+        self.types.push(*particle_type);
+        self.phases.push(Phases::of(particle_type));
+        self.last_type = self.types.len() - 1;
+        self.index.insert(key, self.last_type as u16);
+        return self.last_type as u16
+    }
+
+    // compacts the palette if adding another extra types could take it past its limit, or once it's doubled since
+    // the last compact. anything that's holding on to palette indices has to call this before getting them
+    pub(crate) fn make_room(&mut self, extra: usize) {
+        if self.types.len() + extra > self.compact_at.min(u16::MAX as usize + 1) {
+            self.compact();
+            self.compact_at = (self.types.len() * 2).max(MIN_COMPACT);
+        }
+    }
+
+    // drops every type no cell uses any more, a grid that's had a lot of different things painted into it over time
+    // would otherwise carry all of them around forever
+    pub(crate) fn compact(&mut self) {
+        let mut used = vec![false; self.types.len()];
+        for index in &self.type_index {
+            used[*index as usize] = true;
+        }
+        let mut remap = vec![0; self.types.len()];
+        let (mut types, mut phases) = (Vec::new(), Vec::new());
+        for i in 0..self.types.len() {
+            if used[i] {
+                remap[i] = types.len() as u16;
+                types.push(self.types[i]);
+                phases.push(self.phases[i]);
+            }
+        }
+        for index in &mut self.type_index {
+            *index = remap[*index as usize];
+        }
+        self.index.retain(|_, index| {
+            let kept = used[*index as usize];
+            *index = remap[*index as usize];
+            return kept
+        });
+        (self.types, self.phases) = (types, phases);
+        self.last_type = 0;
+        self.palette_id = NEXT_PALETTE_ID.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.type_index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.type_index.is_empty()
+    }
+
+    // puts the cell back together into a Particle, fine for the odd lookup but the columns are faster in a loop
+    pub fn get(&self, i: usize) -> Particle {
+        return Particle {
+            particle_type: self.types[self.type_index[i] as usize],
+            energy: self.energy[i],
+            color_noise: self.color_noise[i],
+            crumbled: self.flags[i] & CRUMBLED != 0,
+            velocity: self.velocity[i],
+            moved_stamp: self.moved_stamp[i],
+            body: self.body[i],
+            body_cell: self.body_cell[i],
+        }
+    }
+
+    pub fn set(&mut self, i: usize, particle: Particle) {
+        let index = self.palette_index(&particle.particle_type);
+        self.set_indexed(i, index, &particle);
+    }
+
+    // set() for a particle whose type is already in the palette at index, which saves looking it up
+    pub(crate) fn set_indexed(&mut self, i: usize, index: u16, particle: &Particle) {
+        self.type_index[i] = index;
+        self.energy[i] = particle.energy;
+        self.color_noise[i] = particle.color_noise;
+        self.flags[i] = flags_of(particle);
+        self.velocity[i] = particle.velocity;
+        self.moved_stamp[i] = particle.moved_stamp;
+        self.body[i] = particle.body;
+        self.body_cell[i] = particle.body_cell;
+    }
+
+    pub fn swap(&mut self, i: usize, j: usize) {
+        self.type_index.swap(i, j);
+        self.energy.swap(i, j);
+        self.color_noise.swap(i, j);
+        self.flags.swap(i, j);
+        self.velocity.swap(i, j);
+        self.moved_stamp.swap(i, j);
+        self.body.swap(i, j);
+        self.body_cell.swap(i, j);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Particle> + '_ {
+        return (0..self.len()).map(|i| self.get(i))
+    }
+
+    pub fn particle_type(&self, i: usize) -> &ParticleType {
+        return &self.types[self.type_index[i] as usize]
+    }
+
+    fn phases(&self, i: usize) -> &Phases {
+        return &self.phases[self.type_index[i] as usize]
+    }
+
+    pub fn get_state(&self, i: usize) -> u8 {
+        return self.phases(i).state(self.energy[i], self.flags[i] & CRUMBLED != 0)
+    }
+
+    pub fn get_density(&self, i: usize) -> f32 {
+        return self.phases(i).density(self.energy[i], self.flags[i] & CRUMBLED != 0)
+    }
+
+    pub fn get_temperature_f64(&self, i: usize) -> f64 {
+        return self.phases(i).temperature(self.energy[i])
+    }
+
+    // the palette that type_indices() points into
+    pub fn types(&self) -> &[ParticleType] {
+        return &self.types
+    }
+
+    pub(crate) fn palette_id(&self) -> u64 {
+        return self.palette_id
+    }
+
+    pub fn type_indices(&self) -> &[u16] {
+        return &self.type_index
+    }
+
+    pub fn energies(&self) -> &[u64] {
+        return &self.energy
+    }
+
+    pub fn color_noise(&self) -> &[u8] {
+        return &self.color_noise
+    }
+
+    // how far each particle moved in the last movement pass it took part in, in cells
+    pub fn velocities(&self) -> &[[f16; 2]] {
+        return &self.velocity
+    }
+
+    pub(crate) fn set_energy(&mut self, i: usize, energy: u64) {
+        self.energy[i] = energy;
+    }
+
+    pub(crate) fn set_crumbled(&mut self, i: usize, crumbled: bool) {
+        self.flags[i] = if crumbled { self.flags[i] | CRUMBLED } else { self.flags[i] & !CRUMBLED };
+    }
+
+    pub(crate) fn set_body(&mut self, i: usize, body: u32, body_cell: u32) {
+        self.body[i] = body;
+        self.body_cell[i] = body_cell;
+    }
+
+    pub(crate) fn raw(&mut self) -> RawStorage {
+        return RawStorage {
+            types: self.types.as_ptr(),
+            phases: self.phases.as_ptr(),
+            type_index: self.type_index.as_mut_ptr(),
+            energy: self.energy.as_mut_ptr(),
+            color_noise: self.color_noise.as_mut_ptr(),
+            flags: self.flags.as_mut_ptr(),
+            velocity: self.velocity.as_mut_ptr(),
+            moved_stamp: self.moved_stamp.as_mut_ptr(),
+            body: self.body.as_mut_ptr(),
+            body_cell: self.body_cell.as_mut_ptr(),
+        }
+    }
+}
+
+fn flags_of(particle: &Particle) -> u8 {
+    return if particle.crumbled { CRUMBLED } else { 0 }
+}
+
+// all of these are unsafe for the same reason: i has to be in bounds and no other thread may touch cell i at the same time
+impl RawStorage {
+    pub(crate) unsafe fn particle_type<'a>(&self, i: usize) -> &'a ParticleType {
+        return self.palette_type(*self.type_index.add(i))
+    }
+
+    pub(crate) unsafe fn phases<'a>(&self, i: usize) -> &'a Phases {
+        return self.palette_phases(*self.type_index.add(i))
+    }
+
+    // these two go by palette index rather than cell, which has to be in the palette
+    pub(crate) unsafe fn palette_type<'a>(&self, index: u16) -> &'a ParticleType {
+        return &*self.types.add(index as usize)
+    }
+
+    pub(crate) unsafe fn palette_phases<'a>(&self, index: u16) -> &'a Phases {
+        return &*self.phases.add(index as usize)
+    }
+
+    pub(crate) unsafe fn energy(&self, i: usize) -> u64 {
+        return *self.energy.add(i)
+    }
+
+    pub(crate) unsafe fn set_energy(&self, i: usize, energy: u64) {
+        *self.energy.add(i) = energy;
+    }
+
+    pub(crate) unsafe fn crumbled(&self, i: usize) -> bool {
+        return *self.flags.add(i) & CRUMBLED != 0
+    }
+
+    pub(crate) unsafe fn set_velocity(&self, i: usize, velocity: [f16; 2]) {
+        *self.velocity.add(i) = velocity;
+    }
+
+    pub(crate) unsafe fn moved_stamp(&self, i: usize) -> u32 {
+        return *self.moved_stamp.add(i)
+    }
+
+    pub(crate) unsafe fn set_moved_stamp(&self, i: usize, stamp: u32) {
+        *self.moved_stamp.add(i) = stamp;
+    }
+
+    // type_index has to be where particle's type is in the palette
+    pub(crate) unsafe fn set(&self, i: usize, type_index: u16, particle: &Particle) {
+        *self.type_index.add(i) = type_index;
+        *self.energy.add(i) = particle.energy;
+        *self.color_noise.add(i) = particle.color_noise;
+        *self.flags.add(i) = flags_of(particle);
+        *self.velocity.add(i) = particle.velocity;
+        *self.moved_stamp.add(i) = particle.moved_stamp;
+        *self.body.add(i) = particle.body;
+        *self.body_cell.add(i) = particle.body_cell;
+    }
+
+    pub(crate) unsafe fn swap(&self, i: usize, j: usize) {
+        std::ptr::swap(self.type_index.add(i), self.type_index.add(j));
+        std::ptr::swap(self.energy.add(i), self.energy.add(j));
+        std::ptr::swap(self.color_noise.add(i), self.color_noise.add(j));
+        std::ptr::swap(self.flags.add(i), self.flags.add(j));
+        std::ptr::swap(self.velocity.add(i), self.velocity.add(j));
+        std::ptr::swap(self.moved_stamp.add(i), self.moved_stamp.add(j));
+        std::ptr::swap(self.body.add(i), self.body.add(j));
+        std::ptr::swap(self.body_cell.add(i), self.body_cell.add(j));
+    }
+}
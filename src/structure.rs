@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+
+use crate::boundary::{Boundary, Edge};
+use crate::chunks::CHUNK_SIZE;
+use crate::particle_sim::ParticleSim;
+
+impl ParticleSim {
+    fn is_static_solid(&self, x: usize, y: usize) -> bool {
+        return self.particle_exists(x, y) && self.particle_at(x, y).get_state() == 0 && self.particle_at(x, y).get_body() == 0
+    }
+
+    // a static solid holds up everything it's joined to if it's made of an anchored material or sits against a
+    // wall. the other boundaries don't hold anything up, things fall out of a void and through a wrapped edge
+    fn holds_up(&self, x: usize, y: usize) -> bool {
+        let wall = |edge: Edge| matches!(self.boundary(edge), Boundary::Wall);
+        return self.particle_at(x, y).particle_type.anchored
+            || (x == 0 && wall(Edge::Left))
+            || (x == self.width - 1 && wall(Edge::Right))
+            || (y == 0 && wall(Edge::Top))
+            || (y == self.height - 1 && wall(Edge::Bottom))
+    }
+
+    // finds chunks of static solids that aren't held up by anything and lets them fall. a chunk no bigger than the
+    // weakest material's strength falls as a rigid body, anything bigger crumbles into powder. only the solids in
+    // awake chunks get looked at, anything that could have taken a solid's support away woke the chunks around it
+    pub fn simulate_structure(&mut self, t: u64) {
+        self.count_pass(t);
+
+        let mut visited: HashSet<(usize, usize)> = HashSet::new();
+        for cy in 0..self.chunks.rows {
+            for cx in 0..self.chunks.cols {
+                if !self.chunks.is_active(cx, cy) {
+                    continue
+                }
+                for y in cy * CHUNK_SIZE..((cy + 1) * CHUNK_SIZE).min(self.height) {
+                    for x in cx * CHUNK_SIZE..((cx + 1) * CHUNK_SIZE).min(self.width) {
+                        if !self.is_static_solid(x, y) || visited.contains(&(x, y)) {
+                            continue
+                        }
+                        let island = self.flood_fill_solid(x, y, |p| p.get_body() == 0);
+                        visited.extend(island.iter().copied());
+                        if island.iter().any(|(ix, iy)| self.holds_up(*ix, *iy)) {
+                            continue
+                        }
+
+                        let strength = island.iter().map(|(ix, iy)| self.particle_at(*ix, *iy).particle_type.strength).min().unwrap_or(0);
+                        if island.len() as u32 <= strength {
+                            self.add_body(island, [0.0, 0.0], 0.0);
+                        } else {
+                            for (ix, iy) in island {
+                                self.particles.set_crumbled(ix + iy * self.width, true);
+                                self.wake(ix, iy);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
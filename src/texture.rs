@@ -1,8 +1,11 @@
-pub fn random(strength_divider: u8) -> u8 {
+use rand::Rng;
+
+// pass in ParticleSim::rng() to keep the noise reproducible along with the rest of the simulation
+pub fn random(rng: &mut impl Rng, strength_divider: u8) -> u8 {
     if strength_divider == 0 {
         return 128;
     }
-    return rand::random::<u8>() / strength_divider + 128
+    return rng.gen::<u8>() / strength_divider + 128
 }
 
 pub fn metal(strength_divider: u8, size: u32, x: u32, y: u32) -> u8 {
@@ -16,3 +19,183 @@ pub fn metal(strength_divider: u8, size: u32, x: u32, y: u32) -> u8 {
 
     return ((val * 255/size) as u8) / strength_divider + 128
 }
+
+// a texture attached to a ParticleType. every particle of that type that gets placed (set_particle, paint, a new or
+// resized grid, an open edge, a World's generator) has its color_noise picked from the pattern at its position, so
+// the same seed always gives the same picture and the pattern lines up across particles placed at different times
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Texture {
+    pub pattern: Pattern,
+    pub strength: u8, // how far color_noise goes from 128 either way, 0 leaves it flat
+    pub seed: u64,
+}
+
+// every pattern comes out between 0.0 and 1.0, 0.5 is the particle's plain colour
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pattern {
+    Random, // a different value for every cell
+    Metal { size: u32 }, // diagonal stripes size cells apart, like metal() but darkening as much as it brightens
+    ValueNoise { scale: f32 }, // smooth blobs about scale cells across
+    Perlin { scale: f32, octaves: u8 }, // gradient noise, each octave adds detail at half the size
+    Wood { ring_spacing: f32, grain: f32 }, // wavy bands ring_spacing cells apart, grain is how far they wander
+    Brick { width: u32, height: u32, mortar: u32 }, // bricks with every other row shifted half a brick, dark mortar lines
+    Crystal { size: f32 }, // flat facets about size cells across with darker edges between them
+    Speckle { density: f32 }, // bright flecks on an even background, density is the share of cells that get one
+}
+
+impl Texture {
+    pub fn new(pattern: Pattern, strength: u8, seed: u64) -> Texture {
+        return Texture { pattern, strength, seed }
+    }
+
+    // the pattern at a position, 0.0 to 1.0
+    pub fn value(&self, x: i64, y: i64) -> f32 {
+        let seed = self.seed;
+        let (fx, fy) = (x as f32, y as f32);
+        let value = match self.pattern {
+            Pattern::Random => unit(hash(seed, x, y)),
+            Pattern::Metal { size } => {
+                let size = size.max(1) as i64;
+                let stripe = (x + y).rem_euclid(size);
+                stripe.min(size - stripe) as f32 * 2.0 / size as f32
+            }
+            Pattern::ValueNoise { scale } => value_noise(seed, fx / scale, fy / scale),
+            Pattern::Perlin { scale, octaves } => fractal(seed, fx / scale, fy / scale, octaves),
+            Pattern::Wood { ring_spacing, grain } => {
+                let wander = (perlin(seed, fx / 24.0, fy / 6.0) - 0.5) * grain;
+                let rings = (fy + wander * ring_spacing) / ring_spacing;
+                // sharp dark edge on one side of each band and a slow fade on the other, like growth rings
+                let band = rings - rings.floor();
+                0.25 + 0.5 * band * band + 0.1 * (unit(hash(seed, x, y)) - 0.5)
+            }
+            Pattern::Brick { width, height, mortar } => {
+                let (width, height) = (width.max(1) as i64, height.max(1) as i64);
+                let row = y.div_euclid(height);
+                let shifted = x + if row.rem_euclid(2) == 1 { width / 2 } else { 0 };
+                let column = shifted.div_euclid(width);
+                let (bx, by) = (shifted.rem_euclid(width), y.rem_euclid(height));
+                if bx < mortar as i64 || by < mortar as i64 {
+                    0.0
+                } else {
+                    // each brick a little different, and a little rough
+                    0.4 + 0.4 * unit(hash(seed, column, row)) + 0.1 * unit(hash(seed ^ 1, x, y))
+                }
+            }
+            Pattern::Crystal { size } => {
+                let (nearest, second, cell) = worley(seed, fx / size, fy / size);
+                let facet = 0.35 + 0.5 * unit(hash(seed, cell.0, cell.1));
+                let edge = ((second - nearest) * 4.0).min(1.0);
+                facet * edge
+            }
+            Pattern::Speckle { density } => {
+                if unit(hash(seed, x, y)) < density { 1.0 } else { 0.5 }
+            }
+        };
+        return value.clamp(0.0, 1.0)
+    }
+
+    // why ParticleType::validate won't take this, if it won't
+    pub(crate) fn problem(&self) -> Option<&'static str> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        return match self.pattern {
+            Pattern::Metal { size: 0 } => Some("a metal texture needs a size of at least 1"),
+            Pattern::ValueNoise { scale } | Pattern::Perlin { scale, .. } if !positive(scale) => Some("a noise texture's scale has to be positive"),
+            Pattern::Wood { ring_spacing, grain } if !positive(ring_spacing) || !grain.is_finite() => {
+                Some("a wood texture needs a positive ring_spacing and a finite grain")
+            }
+            Pattern::Brick { width, height, .. } if width == 0 || height == 0 => Some("a brick texture's bricks can't be 0 wide or high"),
+            Pattern::Crystal { size } if !positive(size) => Some("a crystal texture's size has to be positive"),
+            Pattern::Speckle { density } if !(0.0..=1.0).contains(&density) => Some("a speckle texture's density has to be between 0.0 and 1.0"),
+            _ => None,
+        }
+    }
+
+    // what goes into a particle's color_noise
+    pub fn sample(&self, x: i64, y: i64) -> u8 {
+        return (128.0 + (self.value(x, y) - 0.5) * 2.0 * self.strength as f32).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+// splitmix64 over the seed and both coordinates, the same mixing chunk_update uses for its chunk seeds
+fn hash(seed: u64, x: i64, y: i64) -> u64 {
+    let mut z = seed ^ (x as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ (y as u64).wrapping_mul(0xC2B2AE3D27D4EB4F);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return z ^ (z >> 31)
+}
+
+// the top 24 bits as 0.0 to 1.0
+fn unit(hash: u64) -> f32 {
+    return (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smooth(t: f32) -> f32 {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    return a + (b - a) * t
+}
+
+// random values on the whole numbers, blended smoothly in between
+pub fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let corner = |cx: i64, cy: i64| unit(hash(seed, cx, cy));
+    let top = lerp(corner(ix, iy), corner(ix + 1, iy), tx);
+    let bottom = lerp(corner(ix, iy + 1), corner(ix + 1, iy + 1), tx);
+    return lerp(top, bottom, ty)
+}
+
+// perlin's gradient noise, 0.0 to 1.0 with 0.5 on every whole number
+pub fn perlin(seed: u64, x: f32, y: f32) -> f32 {
+    const D: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    const GRADIENTS: [(f32, f32); 8] = [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0), (D, D), (-D, D), (D, -D), (-D, -D)];
+    let (x0, y0) = (x.floor(), y.floor());
+    let (dx, dy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let dot = |cx: i64, cy: i64, ox: f32, oy: f32| {
+        let (gx, gy) = GRADIENTS[(hash(seed, ix + cx, iy + cy) % 8) as usize];
+        return gx * (dx - ox) + gy * (dy - oy)
+    };
+    let (tx, ty) = (smooth(dx), smooth(dy));
+    let top = lerp(dot(0, 0, 0.0, 0.0), dot(1, 0, 1.0, 0.0), tx);
+    let bottom = lerp(dot(0, 1, 0.0, 1.0), dot(1, 1, 1.0, 1.0), tx);
+    // the raw value stays within about -0.71 to 0.71
+    return (lerp(top, bottom, ty) * D + 0.5).clamp(0.0, 1.0)
+}
+
+// octaves of perlin noise, each one twice as fine and half as strong as the last
+pub fn fractal(seed: u64, x: f32, y: f32, octaves: u8) -> f32 {
+    let (mut total, mut weight, mut frequency, mut amplitude) = (0.0, 0.0, 1.0, 1.0);
+    for octave in 0..octaves.max(1) {
+        total += perlin(seed.wrapping_add(octave as u64), x * frequency, y * frequency) * amplitude;
+        weight += amplitude;
+        frequency *= 2.0;
+        amplitude *= 0.5;
+    }
+    return total / weight
+}
+
+// one random point per grid square. gives the distance to the nearest point, to the second nearest and which square
+// the nearest is in
+fn worley(seed: u64, x: f32, y: f32) -> (f32, f32, (i64, i64)) {
+    let (ix, iy) = (x.floor() as i64, y.floor() as i64);
+    let (mut nearest, mut second, mut cell) = (f32::MAX, f32::MAX, (ix, iy));
+    for cy in iy - 1..=iy + 1 {
+        for cx in ix - 1..=ix + 1 {
+            let h = hash(seed, cx, cy);
+            let (px, py) = (cx as f32 + unit(h), cy as f32 + unit(h.rotate_left(24)));
+            let distance = ((px - x).powi(2) + (py - y).powi(2)).sqrt();
+            if distance < nearest {
+                second = nearest;
+                nearest = distance;
+                cell = (cx, cy);
+            } else if distance < second {
+                second = distance;
+            }
+        }
+    }
+    return (nearest, second, cell)
+}
@@ -0,0 +1,229 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use crate::chunks::CHUNK_SIZE;
+use crate::error::SimError;
+use crate::particle_sim::{Particle, ParticleSim};
+use crate::storage::ParticleStorage;
+
+pub const PAGE_SIZE: usize = 4 * CHUNK_SIZE; // pages are PAGE_SIZE x PAGE_SIZE particles, lined up with the chunks
+
+// a world that goes on in every direction. it's cut into pages and only a window of them around the focus is kept in
+// memory, as one ParticleSim that the passes run on like any other. pages that fall out of the window are written to
+// disk and read back when the window comes around again, pages that have never been visited come from the generator.
+// only what's in the window gets simulated though, the pages outside it are frozen until it comes back to them. its
+// edge acts like the edge of a normal grid: it's a wall, so nothing falls out of the window and nothing comes in from
+// the pages around it, and simulate_structure counts a solid touching it as held up even if the page past it is
+// empty. keep the window big enough that the interesting stuff happens well inside it
+pub struct World {
+    sim: ParticleSim,
+    dir: PathBuf,
+    pages: (usize, usize), // size of the window in pages
+    origin: (i64, i64), // page the window's top left corner is in
+    generator: Box<dyn Fn(i64, i64) -> Particle>,
+}
+
+impl World {
+    // pages are kept as files in dir, a world that was saved there before carries on where it left off.
+    // the generator gets world coordinates and returns what's there before anyone touched it
+    pub fn new(dir: impl Into<PathBuf>, pages_x: usize, pages_y: usize, seed: u64, generator: impl Fn(i64, i64) -> Particle + 'static) -> Result<World, SimError> {
+        if pages_x == 0 || pages_y == 0 {
+            return Err(SimError::InvalidSize { width: pages_x * PAGE_SIZE, height: pages_y * PAGE_SIZE })
+        }
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let origin = (-(pages_x as i64) / 2, -(pages_y as i64) / 2);
+        let fill = generator(origin.0 * PAGE_SIZE as i64, origin.1 * PAGE_SIZE as i64);
+        let sim = ParticleSim::new_seeded(pages_x * PAGE_SIZE, pages_y * PAGE_SIZE, fill, seed);
+        let mut world = World { sim, dir, pages: (pages_x, pages_y), origin, generator: Box::new(generator) };
+
+        let mut particles = ParticleStorage::new(world.sim.particles.len(), fill);
+        for py in 0..pages_y {
+            for px in 0..pages_x {
+                world.bring_in(&mut particles, (px, py))?;
+            }
+        }
+        world.sim.particles = particles;
+        world.sim.wake_all();
+        return Ok(world)
+    }
+
+    // the window everything in memory lives in. its (0, 0) is at origin() in world coordinates
+    pub fn sim(&self) -> &ParticleSim {
+        return &self.sim
+    }
+
+    // anything goes, but the window has to stay the size it was made with. set_focus and save return InvalidSize
+    // if it isn't, after a resize or restoring a snapshot of some other size, say. its boundaries are the window's
+    // edge, so setting them changes what that does instead of the wall
+    pub fn sim_mut(&mut self) -> &mut ParticleSim {
+        return &mut self.sim
+    }
+
+    fn check_window(&self) -> Result<(), SimError> {
+        if (self.sim.width, self.sim.height) != (self.pages.0 * PAGE_SIZE, self.pages.1 * PAGE_SIZE) {
+            return Err(SimError::InvalidSize { width: self.sim.width, height: self.sim.height })
+        }
+        return Ok(())
+    }
+
+    // world coordinates of the window's top left particle
+    pub fn origin(&self) -> (i64, i64) {
+        return (self.origin.0 * PAGE_SIZE as i64, self.origin.1 * PAGE_SIZE as i64)
+    }
+
+    // where a world position is in the window, None if it isn't loaded
+    pub fn to_local(&self, x: i64, y: i64) -> Option<(usize, usize)> {
+        let (ox, oy) = self.origin();
+        let (lx, ly) = (x - ox, y - oy);
+        if lx < 0 || ly < 0 || lx >= self.sim.width as i64 || ly >= self.sim.height as i64 {
+            return None
+        }
+        return Some((lx as usize, ly as usize))
+    }
+
+    pub fn particle_at(&self, x: i64, y: i64) -> Option<Particle> {
+        return self.to_local(x, y).map(|(lx, ly)| self.sim.particle_at(lx, ly))
+    }
+
+    // false if the spot isn't loaded right now
+    pub fn set_particle(&mut self, x: i64, y: i64, particle: Particle) -> bool {
+        return match self.to_local(x, y) {
+            Some((lx, ly)) => {
+                self.sim.set_particle(lx, ly, particle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // moves the window so the page with (x, y) in it is in the middle. pages that drop out get saved, pages that come
+    // into view get loaded or generated. rigid bodies that were partly in a dropped page are broken up first, rigid
+    // materials form new bodies on their own once they're simulated again. the snapshot history is cleared because
+    // it's in window coordinates, and for the same reason a replay recorded across a move won't play back right
+    pub fn set_focus(&mut self, x: i64, y: i64) -> Result<(), SimError> {
+        self.check_window()?;
+        let (pages_x, pages_y) = self.pages;
+        let origin = (
+            x.div_euclid(PAGE_SIZE as i64) - pages_x as i64 / 2,
+            y.div_euclid(PAGE_SIZE as i64) - pages_y as i64 / 2,
+        );
+        if origin == self.origin {
+            return Ok(())
+        }
+        let (shift_x, shift_y) = (origin.0 - self.origin.0, origin.1 - self.origin.1);
+        let kept = |px: usize, py: usize| -> bool {
+            let (nx, ny) = (px as i64 - shift_x, py as i64 - shift_y);
+            return nx >= 0 && ny >= 0 && nx < pages_x as i64 && ny < pages_y as i64
+        };
+
+        let mut ids: Vec<u32> = self.sim.bodies.keys().copied().collect();
+        ids.sort();
+        for id in ids {
+            if self.sim.bodies[&id].cells.iter().any(|(cx, cy)| !kept(cx / PAGE_SIZE, cy / PAGE_SIZE)) {
+                self.sim.let_go_of_body(id);
+            }
+        }
+        for py in 0..pages_y {
+            for px in 0..pages_x {
+                if !kept(px, py) {
+                    self.save_page((px, py))?;
+                }
+            }
+        }
+
+        self.origin = origin;
+        let fill = self.sim.particles.get(0);
+        let mut particles = ParticleStorage::new(self.sim.particles.len(), fill);
+        let (dx, dy) = (shift_x * PAGE_SIZE as i64, shift_y * PAGE_SIZE as i64);
+        for py in 0..pages_y {
+            for px in 0..pages_x {
+                let (old_px, old_py) = (px as i64 + shift_x, py as i64 + shift_y);
+                if old_px >= 0 && old_py >= 0 && old_px < pages_x as i64 && old_py < pages_y as i64 {
+                    for y in 0..PAGE_SIZE {
+                        for x in 0..PAGE_SIZE {
+                            let (wx, wy) = (px * PAGE_SIZE + x, py * PAGE_SIZE + y);
+                            let (ox, oy) = ((wx as i64 + dx) as usize, (wy as i64 + dy) as usize);
+                            particles.set(wx + wy * self.sim.width, self.sim.particles.get(ox + oy * self.sim.width));
+                        }
+                    }
+                } else {
+                    self.bring_in(&mut particles, (px, py))?;
+                }
+            }
+        }
+        self.sim.particles = particles;
+        for body in self.sim.bodies.values_mut() {
+            body.x -= dx as f32;
+            body.y -= dy as f32;
+            for (cx, cy) in &mut body.cells {
+                *cx = (*cx as i64 - dx) as usize;
+                *cy = (*cy as i64 - dy) as usize;
+            }
+        }
+        self.sim.clear_history();
+        self.sim.wake_all();
+        return Ok(())
+    }
+
+    // writes every loaded page out, do this before dropping the world or whatever changed since it was loaded is lost
+    pub fn save(&self) -> Result<(), SimError> {
+        self.check_window()?;
+        for py in 0..self.pages.1 {
+            for px in 0..self.pages.0 {
+                self.save_page((px, py))?;
+            }
+        }
+        return Ok(())
+    }
+
+    fn page_path(&self, page_x: i64, page_y: i64) -> PathBuf {
+        return self.dir.join(format!("page_{}_{}.psim", page_x, page_y))
+    }
+
+    // page is in window pages, (0, 0) is the top left one
+    fn save_page(&self, page: (usize, usize)) -> Result<(), SimError> {
+        let (x0, y0) = (page.0 * PAGE_SIZE, page.1 * PAGE_SIZE);
+        let mut out = ParticleSim::new_seeded(PAGE_SIZE, PAGE_SIZE, self.sim.particle_at(x0, y0), 0);
+        for y in 0..PAGE_SIZE {
+            for x in 0..PAGE_SIZE {
+                let mut particle = self.sim.particle_at(x0 + x, y0 + y);
+                particle.body = 0;
+                particle.body_cell = 0;
+                out.particles.set(x + y * PAGE_SIZE, particle);
+            }
+        }
+        let path = self.page_path(self.origin.0 + page.0 as i64, self.origin.1 + page.1 as i64);
+        return out.write_to(&mut BufWriter::new(File::create(path)?))
+    }
+
+    // fills a page of the window from disk if it was saved before, or from the generator if it's new
+    fn bring_in(&self, particles: &mut ParticleStorage, page: (usize, usize)) -> Result<(), SimError> {
+        let (x0, y0) = (page.0 * PAGE_SIZE, page.1 * PAGE_SIZE);
+        let (page_x, page_y) = (self.origin.0 + page.0 as i64, self.origin.1 + page.1 as i64);
+        let path = self.page_path(page_x, page_y);
+        if path.exists() {
+            let saved = ParticleSim::read_from(&mut BufReader::new(File::open(path)?))?;
+            if saved.width != PAGE_SIZE || saved.height != PAGE_SIZE {
+                return Err(SimError::Format(format!("page ({}, {}) on disk isn't {}x{}", page_x, page_y, PAGE_SIZE, PAGE_SIZE)))
+            }
+            for y in 0..PAGE_SIZE {
+                for x in 0..PAGE_SIZE {
+                    let mut particle = saved.particle_at(x, y);
+                    particle.moved_stamp = 0;
+                    particles.set(x0 + x + (y0 + y) * self.sim.width, particle);
+                }
+            }
+        } else {
+            let (wx, wy) = (page_x * PAGE_SIZE as i64, page_y * PAGE_SIZE as i64);
+            for y in 0..PAGE_SIZE {
+                for x in 0..PAGE_SIZE {
+                    let (gx, gy) = (wx + x as i64, wy + y as i64);
+                    particles.set(x0 + x + (y0 + y) * self.sim.width, (self.generator)(gx, gy).textured(gx, gy));
+                }
+            }
+        }
+        return Ok(())
+    }
+}
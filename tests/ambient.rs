@@ -0,0 +1,59 @@
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+fn rock() -> ParticleType {
+    ParticleType { solid: true, liquid_density: 2.5, melting_temperature: 1500, boiling_temperature: 3000, ..ParticleType::new(1) }
+}
+
+fn filled(temperature: u32) -> ParticleSim {
+    ParticleSim::new_seeded(16, 16, Particle::new(rock()).set_temperature(temperature), 4)
+}
+
+#[test]
+fn nothing_cools_without_a_rate() {
+    let mut sim = filled(300);
+    sim.paint(8, 8, 4, Particle::new(rock()).set_temperature(1200));
+    sim.set_ambient_temperature(200);
+    let before = sim.total_energy();
+    for t in 0..20 {
+        sim.simulate_heat(t);
+    }
+    assert_eq!(sim.total_energy(), before);
+}
+
+#[test]
+fn hot_and_cold_both_head_for_ambient() {
+    for start in [1200, 50] {
+        let mut sim = filled(start);
+        sim.set_ambient_temperature(400);
+        sim.set_cooling_rate(&rock(), 0.1);
+        let mut gap = (start as f32 - 400.0).abs();
+        for t in 0..100 {
+            sim.simulate_heat(t);
+            let now = (sim.stats().mean_temperature - 400.0).abs();
+            assert!(now < gap, "starting at {} the gap went from {} to {} on tick {}", start, gap, now, t);
+            gap = now;
+        }
+        assert!(gap < 1.0, "starting at {} it's still {} away", start, gap);
+    }
+}
+
+#[test]
+fn the_rate_is_clamped_and_zero_turns_it_off() {
+    let mut sim = filled(1000);
+    sim.set_ambient_temperature(300);
+    sim.set_cooling_rate(&rock(), 5.0);
+    assert_eq!(sim.get_cooling_rate(&rock()), 1.0);
+    // every cell snaps to ambient on its turn, but neighbours that go after it still hand it some of their heat
+    for t in 0..4 {
+        sim.simulate_heat(t);
+    }
+    let stats = sim.stats();
+    assert!(stats.min_temperature >= 299.9 && stats.max_temperature < 301.0, "{:?}", stats);
+
+    sim.set_cooling_rate(&rock(), 0.0);
+    assert_eq!(sim.get_cooling_rate(&rock()), 0.0);
+    sim.set_ambient_temperature(900);
+    let before = sim.total_energy();
+    sim.simulate_heat(4);
+    assert_eq!(sim.total_energy(), before);
+}
@@ -0,0 +1,107 @@
+use simple_particle_sim::blackbody::{blackbody_color, Glow};
+use simple_particle_sim::particle_sim::{Particle, ParticleType};
+
+fn rounded(temperature: f32) -> [u8; 3] {
+    blackbody_color(temperature).map(|c| c.round() as u8)
+}
+
+fn particle(temperature: u32) -> Particle {
+    let particle_type = ParticleType {
+        vapor_color: [0, 0, 0, 255],
+        liquid_color: [0, 0, 0, 255],
+        solid_color: [0, 0, 0, 255],
+        solid: true,
+        liquid_density: 7.0,
+        gas_density: 7.0,
+        melting_temperature: 60000,
+        boiling_temperature: 65000,
+        heat_capacity: 450,
+        ..ParticleType::new(0)
+    };
+    Particle::new(particle_type).set_temperature(temperature).set_noise_value(128)
+}
+
+// sRGB values from mitchell charity's blackbody table, 10 degree observer
+#[test]
+fn matches_reference_values() {
+    let reference: [(f32, [u8; 3]); 9] = [
+        (1000.0, [255, 56, 0]),
+        (1500.0, [255, 108, 0]),
+        (2000.0, [255, 137, 18]),
+        (3000.0, [255, 180, 107]),
+        (4000.0, [255, 209, 163]),
+        (5000.0, [255, 228, 206]),
+        (6600.0, [254, 249, 255]),
+        (8000.0, [227, 233, 255]),
+        (10000.0, [204, 219, 255]),
+    ];
+    for (temperature, expected) in reference {
+        let color = rounded(temperature);
+        for i in 0..3 {
+            assert!(
+                (color[i] as i32 - expected[i] as i32).abs() <= 1,
+                "{} K came out {:?}, expected {:?}", temperature, color, expected
+            );
+        }
+    }
+}
+
+#[test]
+fn is_continuous() {
+    let mut previous = blackbody_color(500.0);
+    for temperature in 501..12000 {
+        let color = blackbody_color(temperature as f32);
+        for i in 0..3 {
+            assert!((color[i] - previous[i]).abs() < 0.2, "jump at {} K: {:?} to {:?}", temperature, previous, color);
+        }
+        previous = color;
+    }
+}
+
+#[test]
+fn gets_bluer_as_it_heats_up() {
+    let mut previous = blackbody_color(600.0);
+    for temperature in (700..10000).step_by(100) {
+        let color = blackbody_color(temperature as f32);
+        assert!(color[2] >= previous[2] && color[0] <= previous[0], "{} K", temperature);
+        previous = color;
+    }
+}
+
+#[test]
+fn clamps_outside_the_table() {
+    assert_eq!(rounded(0.0), [255, 0, 0]);
+    assert_eq!(rounded(50000.0), [204, 219, 255]);
+    assert_eq!(rounded(1e9), [204, 219, 255]);
+}
+
+#[test]
+fn nothing_glows_below_the_start_temperature() {
+    let glow = Glow { start_temperature: 1000.0, full_temperature: 2000.0, exponent: 1.0 };
+    assert_eq!(glow.color(999.0), [0.0; 3]);
+    assert_eq!(glow.intensity(1000.0), 0.0);
+    assert!(glow.intensity(1001.0) > 0.0);
+    assert_eq!(particle(900).get_color_over([0, 0, 0, 255], &glow), [0, 0, 0, 255]);
+    assert_ne!(particle(1500).get_color_over([0, 0, 0, 255], &glow), [0, 0, 0, 255]);
+}
+
+#[test]
+fn intensity_follows_the_curve() {
+    let linear = Glow { start_temperature: 1000.0, full_temperature: 3000.0, exponent: 1.0 };
+    let curved = Glow { exponent: 2.0, ..linear };
+    assert!((linear.intensity(2000.0) - 0.5).abs() < 1e-6);
+    assert!((curved.intensity(2000.0) - 0.25).abs() < 1e-6);
+    assert!((linear.intensity(3000.0) - 1.0).abs() < 1e-6);
+    assert!(linear.intensity(5000.0) > 1.0);
+}
+
+#[test]
+fn black_particle_glows_with_the_blackbody_colour() {
+    let glow = Glow::default();
+    let color = particle(3000).get_color_over([0, 0, 0, 255], &glow);
+    let expected = rounded(3000.0);
+    for i in 0..3 {
+        assert!((color[i] as i32 - expected[i] as i32).abs() <= 1, "{:?} vs {:?}", color, expected);
+    }
+    assert_eq!(particle(300).get_color(), [0, 0, 0]);
+}
@@ -0,0 +1,306 @@
+use simple_particle_sim::boundary::{Boundary, Edge};
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+const AIR: u32 = 0;
+const SAND: u32 = 1;
+const WATER: u32 = 2;
+const SMOKE: u32 = 3;
+const STONE: u32 = 4;
+
+fn particle_type(id: u32) -> ParticleType {
+    let (solid, liquid_density, gas_density, melting_temperature, boiling_temperature) = match id {
+        AIR => (false, 0.001, 0.0012, 10, 20),
+        SAND => (false, 1.5, 1.5, 1500, 3000),
+        WATER => (false, 1.0, 0.0006, 273, 373),
+        SMOKE => (false, 0.002, 0.002, 1, 2),
+        _ => (true, 2.5, 2.5, 1500, 3000),
+    };
+    ParticleType {
+        vapor_color: [200, 200, 200, 100],
+        liquid_color: [0, 0, 255, 255],
+        solid_color: [120, 120, 120, 255],
+        solid,
+        liquid_density,
+        gas_density,
+        melting_temperature,
+        boiling_temperature,
+        ..ParticleType::new(id)
+    }
+}
+
+fn particle(id: u32, temperature: u32) -> Particle {
+    Particle::new(particle_type(id)).set_temperature(temperature)
+}
+
+fn grid(fill: u32) -> ParticleSim {
+    ParticleSim::new_seeded(8, 8, particle(fill, 300), 7)
+}
+
+fn count(sim: &ParticleSim, id: u32) -> usize {
+    sim.particles.iter().filter(|p| p.particle_type.id == id).count()
+}
+
+fn total_energy(sim: &ParticleSim) -> u64 {
+    sim.particles.energies().iter().sum()
+}
+
+fn run(sim: &mut ParticleSim, ticks: u64, pass: fn(&mut ParticleSim, u64)) {
+    for t in 0..ticks {
+        pass(sim, t);
+    }
+}
+
+#[test]
+fn bounds_include_the_last_row_and_column() {
+    let sim = ParticleSim::new_seeded(4, 3, particle(AIR, 300), 0);
+    assert!(sim.particle_exists(3, 2));
+    assert!(!sim.particle_exists(4, 0));
+    assert!(!sim.particle_exists(0, 3));
+}
+
+#[test]
+fn wrapping_one_edge_wraps_the_opposite_one() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Left, Boundary::Wrap);
+    assert!(matches!(sim.boundary(Edge::Right), Boundary::Wrap));
+    sim.set_boundary(Edge::Right, Boundary::Void(particle(AIR, 300)));
+    assert!(matches!(sim.boundary(Edge::Left), Boundary::Wall));
+    assert!(matches!(sim.boundary(Edge::Top), Boundary::Wall));
+}
+
+#[test]
+fn boundaries_are_saved() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Top, Boundary::Open(particle(WATER, 300)));
+    sim.set_boundary(Edge::Left, Boundary::Wrap);
+    let mut bytes = Vec::new();
+    sim.write_to(&mut bytes).unwrap();
+    let loaded = ParticleSim::read_from(&mut bytes.as_slice()).unwrap();
+    assert!(matches!(loaded.boundary(Edge::Top), Boundary::Open(p) if p.particle_type.id == WATER));
+    assert!(matches!(loaded.boundary(Edge::Right), Boundary::Wrap));
+    assert!(matches!(loaded.boundary(Edge::Bottom), Boundary::Wall));
+}
+
+#[test]
+fn wall_sand_lands_on_the_bottom_row() {
+    let mut sim = grid(AIR);
+    sim.set_particle(3, 0, particle(SAND, 300));
+    sim.set_particle(7, 0, particle(SAND, 300));
+    run(&mut sim, 20, ParticleSim::simulate_sand);
+    assert_eq!(sim.particle_at(3, 7).particle_type.id, SAND);
+    assert_eq!(sim.particle_at(7, 7).particle_type.id, SAND);
+}
+
+#[test]
+fn wall_keeps_liquids_in() {
+    let mut sim = grid(AIR);
+    sim.set_particle(0, 7, particle(WATER, 300));
+    sim.set_particle(7, 7, particle(WATER, 300));
+    run(&mut sim, 20, ParticleSim::simulate_liquids);
+    assert_eq!(count(&sim, WATER), 2);
+    for x in 0..8 {
+        for y in 0..7 {
+            assert_ne!(sim.particle_at(x, y).particle_type.id, WATER);
+        }
+    }
+}
+
+#[test]
+fn wall_gas_stops_at_the_top_row() {
+    let mut sim = grid(AIR);
+    sim.set_particle(3, 7, particle(SMOKE, 300));
+    run(&mut sim, 20, ParticleSim::simulate_gasses);
+    // it drifts sideways along the ceiling but never gets through it
+    assert_eq!(count(&sim, SMOKE), 1);
+    assert!((0..8).any(|x| sim.particle_at(x, 0).particle_type.id == SMOKE));
+}
+
+#[test]
+fn wall_movement_pass_keeps_everything() {
+    let mut sim = grid(AIR);
+    sim.set_particle(7, 0, particle(SAND, 300));
+    sim.set_particle(0, 3, particle(WATER, 300));
+    sim.set_particle(7, 7, particle(SMOKE, 300));
+    run(&mut sim, 20, ParticleSim::simulate_movement);
+    assert_eq!((count(&sim, SAND), count(&sim, WATER), count(&sim, SMOKE)), (1, 1, 1));
+    assert_eq!(sim.particle_at(7, 7).particle_type.id, SAND);
+}
+
+#[test]
+fn wall_heat_reaches_the_corner_and_is_kept() {
+    let mut sim = grid(STONE);
+    sim.set_particle(7, 7, particle(STONE, 1300));
+    let before = total_energy(&sim);
+    run(&mut sim, 1, ParticleSim::simulate_heat);
+    assert!(sim.particle_at(6, 6).get_temperature() > 300);
+    assert!(sim.particle_at(7, 6).get_temperature() > 300);
+    run(&mut sim, 10, ParticleSim::simulate_heat);
+    assert_eq!(total_energy(&sim), before);
+}
+
+#[test]
+fn wall_simplified_heat_reaches_the_corner() {
+    let mut sim = grid(STONE);
+    sim.set_particle(7, 7, particle(STONE, 1300));
+    let before = total_energy(&sim);
+    run(&mut sim, 1, ParticleSim::simulate_heat_simplified);
+    assert!(sim.particle_at(6, 6).get_temperature() > 300);
+    assert_eq!(total_energy(&sim), before);
+}
+
+#[test]
+fn void_lets_sand_fall_out() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Bottom, Boundary::Void(particle(AIR, 300)));
+    sim.set_particle(3, 5, particle(SAND, 300));
+    run(&mut sim, 5, ParticleSim::simulate_sand);
+    assert_eq!(count(&sim, SAND), 0);
+    assert_eq!(count(&sim, AIR), 64);
+}
+
+#[test]
+fn void_lets_liquids_drain() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Bottom, Boundary::Void(particle(AIR, 300)));
+    sim.set_particle(3, 7, particle(WATER, 300));
+    run(&mut sim, 1, ParticleSim::simulate_liquids);
+    assert_eq!(count(&sim, WATER), 0);
+}
+
+#[test]
+fn void_lets_gas_escape() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Top, Boundary::Void(particle(AIR, 300)));
+    sim.set_particle(3, 2, particle(SMOKE, 300));
+    run(&mut sim, 5, ParticleSim::simulate_gasses);
+    assert_eq!(count(&sim, SMOKE), 0);
+}
+
+#[test]
+fn void_movement_pass_empties_the_grid() {
+    let mut sim = grid(AIR);
+    sim.set_all_boundaries(Boundary::Void(particle(AIR, 300)));
+    sim.set_particle(3, 3, particle(SAND, 300));
+    sim.set_particle(5, 5, particle(WATER, 300));
+    sim.set_particle(1, 1, particle(SMOKE, 300));
+    run(&mut sim, 20, ParticleSim::simulate_movement);
+    assert_eq!(count(&sim, AIR), 64);
+}
+
+#[test]
+fn void_takes_no_heat() {
+    let mut sim = grid(STONE);
+    sim.set_all_boundaries(Boundary::Void(particle(AIR, 0)));
+    sim.set_particle(0, 0, particle(STONE, 1300));
+    let before = total_energy(&sim);
+    run(&mut sim, 10, ParticleSim::simulate_heat);
+    assert_eq!(total_energy(&sim), before);
+}
+
+#[test]
+fn wrap_sand_falls_through_the_floor_to_the_top() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Bottom, Boundary::Wrap);
+    sim.set_particle(3, 7, particle(SAND, 300));
+    run(&mut sim, 1, ParticleSim::simulate_sand);
+    assert_eq!(sim.particle_at(3, 0).particle_type.id, SAND);
+    assert_eq!(count(&sim, SAND), 1);
+}
+
+#[test]
+fn wrap_liquid_falls_through_the_floor_to_the_top() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Top, Boundary::Wrap);
+    sim.set_particle(3, 7, particle(WATER, 300));
+    run(&mut sim, 1, ParticleSim::simulate_liquids);
+    assert_eq!(sim.particle_at(3, 0).particle_type.id, WATER);
+}
+
+#[test]
+fn wrap_gas_rises_through_the_ceiling_to_the_bottom() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Top, Boundary::Wrap);
+    sim.set_particle(3, 0, particle(SMOKE, 300));
+    run(&mut sim, 1, ParticleSim::simulate_gasses);
+    assert_eq!(sim.particle_at(3, 7).particle_type.id, SMOKE);
+}
+
+#[test]
+fn wrap_movement_pass_keeps_everything() {
+    let mut sim = grid(AIR);
+    sim.set_all_boundaries(Boundary::Wrap);
+    sim.set_particle(3, 3, particle(SAND, 300));
+    sim.set_particle(5, 5, particle(WATER, 300));
+    sim.set_particle(1, 1, particle(SMOKE, 300));
+    run(&mut sim, 50, ParticleSim::simulate_movement);
+    assert_eq!((count(&sim, SAND), count(&sim, WATER), count(&sim, SMOKE)), (1, 1, 1));
+}
+
+#[test]
+fn wrap_heat_crosses_the_edge() {
+    let mut walled = grid(STONE);
+    walled.set_particle(0, 3, particle(STONE, 1300));
+    let mut wrapped = walled.clone();
+    wrapped.set_boundary(Edge::Left, Boundary::Wrap);
+    let before = total_energy(&wrapped);
+
+    run(&mut walled, 1, ParticleSim::simulate_heat);
+    run(&mut wrapped, 1, ParticleSim::simulate_heat);
+    assert_eq!(walled.particle_at(7, 3).get_temperature(), 300);
+    assert!(wrapped.particle_at(7, 3).get_temperature() > 300);
+    assert_eq!(total_energy(&wrapped), before);
+
+    let mut simplified = grid(STONE);
+    simplified.set_particle(0, 3, particle(STONE, 1300));
+    simplified.set_boundary(Edge::Left, Boundary::Wrap);
+    run(&mut simplified, 1, ParticleSim::simulate_heat_simplified);
+    assert!(simplified.particle_at(7, 3).get_temperature() > 300);
+}
+
+#[test]
+fn open_edge_pours_in() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Top, Boundary::Open(particle(WATER, 300)));
+    run(&mut sim, 40, ParticleSim::simulate_liquids);
+    assert!(count(&sim, WATER) > 8);
+    assert_eq!(sim.particle_at(3, 7).particle_type.id, WATER);
+}
+
+#[test]
+fn open_edge_takes_what_sinks_out() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Bottom, Boundary::Open(particle(AIR, 300)));
+    sim.set_particle(3, 5, particle(SAND, 300));
+    run(&mut sim, 5, ParticleSim::simulate_sand);
+    assert_eq!(count(&sim, SAND), 0);
+    assert_eq!(sim.particle_at(3, 7).particle_type.id, AIR);
+}
+
+#[test]
+fn open_edge_lets_gas_in_and_out() {
+    let mut sim = grid(AIR);
+    sim.set_boundary(Edge::Bottom, Boundary::Open(particle(SMOKE, 300)));
+    run(&mut sim, 1, ParticleSim::simulate_gasses);
+    assert!(count(&sim, SMOKE) > 0);
+
+    let mut sim = grid(SMOKE);
+    sim.set_boundary(Edge::Top, Boundary::Open(particle(AIR, 300)));
+    run(&mut sim, 20, ParticleSim::simulate_movement);
+    assert!(count(&sim, SMOKE) < 64);
+}
+
+#[test]
+fn open_edge_is_a_heat_reservoir() {
+    let mut sim = grid(STONE);
+    sim.set_boundary(Edge::Left, Boundary::Open(particle(STONE, 1300)));
+    let before = total_energy(&sim);
+    run(&mut sim, 5, ParticleSim::simulate_heat);
+    assert!(sim.particle_at(0, 3).get_temperature() > 300);
+    assert!(sim.particle_at(7, 3).get_temperature() <= sim.particle_at(0, 3).get_temperature());
+    assert!(total_energy(&sim) > before);
+
+    let mut sim = grid(STONE);
+    sim.set_boundary(Edge::Left, Boundary::Open(particle(STONE, 1300)));
+    run(&mut sim, 5, ParticleSim::simulate_heat_simplified);
+    assert!(sim.particle_at(0, 3).get_temperature() > 300);
+}
@@ -0,0 +1,102 @@
+use simple_particle_sim::error::SimError;
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+use simple_particle_sim::texture::{Pattern, Texture};
+
+fn sim() -> ParticleSim {
+    ParticleSim::new_seeded(8, 6, Particle::new(ParticleType::new(0)), 5)
+}
+
+fn out_of_bounds(result: Result<(), SimError>, x: i64, y: i64) -> bool {
+    matches!(result, Err(SimError::OutOfBounds { x: ex, y: ey, width: 8, height: 6 }) if ex == x && ey == y)
+}
+
+#[test]
+fn reads_outside_the_grid_are_none() {
+    let sim = sim();
+    assert!(sim.get(7, 5).is_some());
+    assert!(sim.get(8, 0).is_none());
+    assert!(sim.get(0, 6).is_none());
+    assert!(sim.get_signed(-1, 0).is_none());
+    assert!(sim.get_signed(0, i64::MIN).is_none());
+}
+
+#[test]
+fn writes_outside_the_grid_say_where() {
+    let mut sim = sim();
+    let particle = Particle::new(ParticleType::new(1));
+    assert!(out_of_bounds(sim.try_set(8, 0, particle), 8, 0));
+    assert!(out_of_bounds(sim.try_set_signed(-3, 2, particle), -3, 2));
+    assert!(out_of_bounds(sim.try_set_energy(0, 6, 5), 0, 6));
+    assert!(out_of_bounds(sim.try_set_energy_signed(2, -1, 5), 2, -1));
+    assert!(sim.get_mut(8, 6).is_none());
+    assert!(sim.get_mut_signed(-1, -1).is_none());
+    assert!(sim.particles.iter().all(|p| p.particle_type.id == 0));
+}
+
+#[test]
+fn invalid_types_are_refused() {
+    let mut sim = sim();
+    let broken = [
+        ParticleType { heat_capacity: 0, ..ParticleType::new(1) },
+        ParticleType { melting_temperature: 500, boiling_temperature: 400, ..ParticleType::new(2) },
+        ParticleType { gas_density: f32::NAN, ..ParticleType::new(3) },
+        ParticleType { surface_tension: 1.5, ..ParticleType::new(4) },
+    ];
+    for particle_type in broken {
+        let result = sim.try_set(1, 1, Particle::new(particle_type));
+        assert!(matches!(result, Err(SimError::InvalidMaterial { id, .. }) if id == particle_type.id), "{:?}", result);
+    }
+    assert_eq!(sim.particle_at(1, 1).particle_type.id, 0);
+    assert!(matches!(
+        ParticleSim::try_new_seeded(0, 4, Particle::new(ParticleType::new(0)), 1),
+        Err(SimError::InvalidSize { width: 0, height: 4 })
+    ));
+    assert!(matches!(
+        ParticleSim::try_new_seeded(usize::MAX, 2, Particle::new(ParticleType::new(0)), 1),
+        Err(SimError::InvalidSize { .. })
+    ));
+    assert!(sim.try_set(1, 1, Particle::new(ParticleType::new(9))).is_ok());
+    assert!(sim.try_set_energy(1, 1, 1234).is_ok());
+    assert_eq!(sim.particle_at(1, 1).energy, 1234);
+}
+
+#[test]
+fn get_mut_writes_back_on_drop() {
+    let mut sim = sim();
+    sim.get_mut(3, 2).unwrap().set_temperature(700);
+    assert_eq!(sim.particle_at(3, 2).get_temperature(), 700);
+    assert_eq!(sim.particle_at(2, 2).get_temperature(), 0);
+}
+
+#[test]
+fn get_mut_keeps_a_changed_color_noise() {
+    let textured = ParticleType { texture: Some(Texture::new(Pattern::Random, 40, 9)), ..ParticleType::new(1) };
+    let mut sim = sim();
+    sim.set_particle(4, 4, Particle::new(textured));
+    let noise = sim.particle_at(4, 4).color_noise;
+    let new_noise = noise.wrapping_add(77);
+    sim.get_mut(4, 4).unwrap().color_noise = new_noise;
+    assert_eq!(sim.particle_at(4, 4).color_noise, new_noise);
+
+    sim.get_mut(4, 4).unwrap().set_temperature(400);
+    assert_eq!(sim.particle_at(4, 4).color_noise, new_noise);
+}
+
+#[test]
+fn get_mut_is_recorded() {
+    let textured = ParticleType { texture: Some(Texture::new(Pattern::Random, 40, 9)), ..ParticleType::new(1) };
+    let mut sim = sim();
+    sim.set_particle(4, 4, Particle::new(textured));
+    sim.start_recording();
+    sim.get_mut(4, 4).unwrap().color_noise = 3;
+    sim.get_mut(1, 1).unwrap().set_temperature(500);
+    let untouched = sim.get_mut(2, 2).unwrap().energy;
+    assert_eq!(untouched, 0);
+    let replay = sim.stop_recording().unwrap();
+    assert_eq!(replay.events.len(), 2);
+
+    let (mut played, mut player) = replay.play();
+    player.apply_edits(&mut played).unwrap();
+    assert_eq!(played.particle_at(4, 4), sim.particle_at(4, 4));
+    assert_eq!(played.particle_at(1, 1), sim.particle_at(1, 1));
+}
@@ -0,0 +1,52 @@
+// materials and helpers shared by the test files, not every file uses all of them
+#![allow(dead_code)]
+
+use simple_particle_sim::particle_sim::{ParticleSim, ParticleType};
+
+// every pass, in the order a game would run them
+pub const PASSES: [fn(&mut ParticleSim, u64); 4] = [
+    ParticleSim::simulate_movement,
+    ParticleSim::simulate_heat,
+    ParticleSim::simulate_structure,
+    ParticleSim::simulate_rigid_bodies,
+];
+
+pub fn tick(sim: &mut ParticleSim, t: u64) {
+    for pass in PASSES {
+        pass(sim, t);
+    }
+}
+
+pub fn air() -> ParticleType {
+    ParticleType { liquid_density: 0.001, gas_density: 0.0012, melting_temperature: 10, boiling_temperature: 20, ..ParticleType::new(0) }
+}
+
+pub fn sand() -> ParticleType {
+    ParticleType { liquid_density: 1.5, gas_density: 1.5, melting_temperature: 1500, boiling_temperature: 3000, ..ParticleType::new(1) }
+}
+
+pub fn water() -> ParticleType {
+    ParticleType { liquid_density: 1.0, gas_density: 0.0006, melting_temperature: 273, boiling_temperature: 373, ..ParticleType::new(2) }
+}
+
+pub fn oil() -> ParticleType {
+    ParticleType { liquid_density: 0.9, gas_density: 0.0005, melting_temperature: 200, boiling_temperature: 500, ..ParticleType::new(3) }
+}
+
+pub fn stone() -> ParticleType {
+    ParticleType {
+        solid: true,
+        liquid_density: 2.5,
+        gas_density: 2.5,
+        melting_temperature: 1500,
+        boiling_temperature: 3000,
+        ..ParticleType::new(4)
+    }
+}
+
+// the whole simulation as bytes, for comparing two of them exactly
+pub fn bytes(sim: &ParticleSim) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    sim.write_to(&mut bytes).unwrap();
+    bytes
+}
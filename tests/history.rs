@@ -0,0 +1,114 @@
+mod common;
+
+use common::{air, bytes, sand, tick, water};
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+use simple_particle_sim::region::Anchor;
+
+// rigid, so it falls as a body of its own
+fn stone() -> ParticleType {
+    ParticleType { rigid: true, ..common::stone() }
+}
+
+fn world() -> ParticleSim {
+    let mut sim = ParticleSim::new_seeded(100, 70, Particle::new(air()).set_temperature(300), 21);
+    sim.paint(20, 10, 6, Particle::new(sand()).set_temperature(300));
+    sim.paint(60, 20, 8, Particle::new(water()).set_temperature(360));
+    sim.paint(85, 8, 3, Particle::new(stone()).set_temperature(300));
+    sim
+}
+
+#[test]
+fn rewind_gives_back_every_earlier_state() {
+    let mut sim = world();
+    sim.set_history_capacity(100);
+    let mut states = Vec::new();
+    for t in 0..60 {
+        match t {
+            20 => sim.paint(10, 60, 4, Particle::new(water()).set_temperature(300)),
+            // lots of short lived types so the palette gets compacted along the way
+            30 => {
+                for id in 100..400 {
+                    sim.set_particle(50, 5, Particle::new(ParticleType { id, ..sand() }));
+                }
+            }
+            40 => sim.resize(80, 90, Anchor::BottomRight, Particle::new(air()).set_temperature(300)).unwrap(),
+            _ => {}
+        }
+        sim.push_snapshot();
+        states.push(bytes(&sim));
+        tick(&mut sim, t);
+    }
+    assert_eq!(sim.history_len(), 60);
+
+    assert!(sim.rewind(0));
+    assert_eq!(bytes(&sim), states[59]);
+    for n in [1, 3, 7, 10, 15, 20] {
+        assert!(sim.rewind(n));
+        let expected = &states[sim.history_len() - 1];
+        assert_eq!(&bytes(&sim), expected, "after rewinding {}", n);
+    }
+}
+
+#[test]
+fn carrying_on_after_a_rewind_matches_the_first_run() {
+    let mut sim = world();
+    sim.set_history_capacity(10);
+    for t in 0..30 {
+        sim.push_snapshot();
+        tick(&mut sim, t);
+    }
+    let mut first_run = sim.clone();
+    first_run.set_history_capacity(0);
+
+    for t in 30..35 {
+        sim.push_snapshot();
+        tick(&mut sim, t);
+    }
+    assert!(sim.rewind(4));
+    tick(&mut sim, 30);
+    tick(&mut first_run, 30);
+    assert_eq!(bytes(&sim), bytes(&first_run));
+}
+
+#[test]
+fn only_as_much_history_as_the_capacity() {
+    let mut sim = world();
+    sim.set_history_capacity(5);
+    assert!(!sim.rewind(0));
+    for t in 0..8 {
+        sim.push_snapshot();
+        tick(&mut sim, t);
+    }
+    assert_eq!(sim.history_len(), 5);
+    let before = bytes(&sim);
+    assert!(!sim.rewind(5));
+    assert_eq!(bytes(&sim), before);
+    assert!(sim.rewind(4));
+    assert_eq!(sim.history_len(), 1);
+
+    sim.set_history_capacity(0);
+    sim.push_snapshot();
+    assert_eq!(sim.history_len(), 0);
+}
+
+#[test]
+fn restore_goes_back_to_the_snapshot() {
+    let mut sim = world();
+    for t in 0..5 {
+        tick(&mut sim, t);
+    }
+    let snapshot = sim.snapshot();
+    let expected = bytes(&sim);
+    let mut carried_on = sim.clone();
+    for t in 5..15 {
+        tick(&mut sim, t);
+    }
+    sim.resize(30, 30, Anchor::Center, Particle::new(air())).unwrap();
+    sim.restore(&snapshot);
+    assert_eq!(bytes(&sim), expected);
+
+    // the random generator comes back too
+    tick(&mut sim, 5);
+    tick(&mut carried_on, 5);
+    assert_eq!(bytes(&sim), bytes(&carried_on));
+}
@@ -0,0 +1,81 @@
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+fn water() -> ParticleType {
+    ParticleType { liquid_density: 1.0, gas_density: 0.0006, melting_temperature: 273, boiling_temperature: 373, ..ParticleType::new(1) }
+}
+
+// the same as water but a different colour, so it has nothing pushing it up or down
+fn ink() -> ParticleType {
+    ParticleType { id: 2, liquid_color: [20, 20, 120, 255], ..water() }
+}
+
+fn count(sim: &ParticleSim, id: u32, ys: std::ops::Range<usize>) -> usize {
+    ys.flat_map(|y| (0..sim.width).map(move |x| (x, y))).filter(|(x, y)| sim.particle_at(*x, *y).particle_type.id == id).count()
+}
+
+#[test]
+fn slow_mixing_keeps_its_chunk_awake() {
+    let mut sim = ParticleSim::new_seeded(32, 32, Particle::new(water()).set_temperature(300), 5);
+    for y in 0..16 {
+        for x in 0..32 {
+            sim.set_particle(x, y, Particle::new(ink()).set_temperature(300));
+        }
+    }
+    sim.set_miscibility(&water(), &ink(), 0.1);
+    for t in 0..300 {
+        sim.simulate_movement(t);
+    }
+    // a swap only comes up now and then, without the chunk staying awake for it the ink would stop at the line
+    assert!(count(&sim, ink().id, 16..32) > 32);
+    assert!(count(&sim, water().id, 0..16) > 32);
+    assert!(sim.is_chunk_active(0, 0));
+}
+
+fn oil() -> ParticleType {
+    ParticleType { liquid_density: 0.9, gas_density: 0.0005, melting_temperature: 200, boiling_temperature: 500, ..ParticleType::new(3) }
+}
+
+fn air() -> ParticleType {
+    ParticleType { liquid_density: 0.001, gas_density: 0.0012, melting_temperature: 10, boiling_temperature: 20, ..ParticleType::new(0) }
+}
+
+#[test]
+fn oil_ends_up_in_a_layer_on_water() {
+    let mut sim = ParticleSim::new_seeded(16, 32, Particle::new(water()).set_temperature(300), 5);
+    for y in 16..32 {
+        for x in 0..16 {
+            sim.set_particle(x, y, Particle::new(oil()).set_temperature(300));
+        }
+    }
+    for t in 0..120 {
+        sim.simulate_movement(t);
+    }
+    assert_eq!(count(&sim, oil().id, 0..16), 16 * 16);
+    assert_eq!(count(&sim, water().id, 16..32), 16 * 16);
+
+    // and once it's there it stays put
+    let settled = sim.clone();
+    for t in 120..130 {
+        sim.simulate_movement(t);
+    }
+    assert!((0..32).all(|y| (0..16).all(|x| sim.particle_at(x, y) == settled.particle_at(x, y))));
+    assert!(!sim.is_chunk_active(0, 0));
+}
+
+// how many columns a drop of water dropped onto the floor ends up covering
+fn spread(surface_tension: f32) -> usize {
+    let drop = ParticleType { surface_tension, ..water() };
+    let mut sim = ParticleSim::new_seeded(64, 32, Particle::new(air()).set_temperature(300), 5);
+    sim.paint(32, 24, 3, Particle::new(drop).set_temperature(300));
+    for t in 0..100 {
+        sim.simulate_movement(t);
+    }
+    (0..64).filter(|x| (0..32).any(|y| sim.particle_at(*x, y).particle_type.id == drop.id)).count()
+}
+
+#[test]
+fn surface_tension_holds_a_drop_together() {
+    // without it the drop runs out into a film one cell thick
+    let (loose, held) = (spread(0.0), spread(0.95));
+    assert!(held * 3 / 2 < loose, "{} columns with surface tension, {} without", held, loose);
+}
@@ -0,0 +1,42 @@
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+fn sim() -> ParticleSim {
+    ParticleSim::new_seeded(16, 16, Particle::new(ParticleType::new(0)), 1)
+}
+
+#[test]
+fn same_type_is_stored_once() {
+    let mut sim = sim();
+    let nan = ParticleType { surface_tension: f32::NAN, ..ParticleType::new(1) };
+    for x in 0..16 {
+        sim.set_particle(x, 0, Particle::new(ParticleType::new(2)));
+        sim.set_particle(x, 1, Particle::new(nan));
+    }
+    assert_eq!(sim.particles.types().len(), 3);
+}
+
+#[test]
+fn unused_types_are_dropped_between_passes() {
+    let mut sim = sim();
+    for id in 1..2000 {
+        sim.set_particle(3, 3, Particle::new(ParticleType::new(id)));
+    }
+    sim.simulate_heat(0);
+    assert!(sim.particles.types().len() <= 4, "{} types left", sim.particles.types().len());
+    assert_eq!(sim.particle_at(3, 3).particle_type.id, 1999);
+    assert_eq!(sim.particle_at(0, 0).particle_type.id, 0);
+}
+
+#[test]
+fn more_than_65536_types_over_time() {
+    let mut sim = sim();
+    for id in 1..70000 {
+        sim.set_particle((id % 16) as usize, 5, Particle::new(ParticleType::new(id)));
+    }
+    assert!(sim.particles.types().len() <= 65536);
+    for x in 0..16 {
+        assert_eq!(sim.particle_at(x, 5).particle_type.id % 16, x as u32);
+        assert!(sim.particle_at(x, 5).particle_type.id > 69000);
+    }
+    assert_eq!(sim.particle_at(0, 0).particle_type.id, 0);
+}
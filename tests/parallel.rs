@@ -0,0 +1,51 @@
+#![cfg(feature = "rayon")]
+
+mod common;
+
+use common::{air, bytes, oil, sand, tick, water};
+use rand::Rng;
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+// strong enough that the unsupported slab falls as a body
+fn stone() -> ParticleType {
+    ParticleType { strength: 50, ..common::stone() }
+}
+
+// several chunks of everything: falling sand, liquids that mix, water boiling off a hot floor and a slab of stone
+// with nothing under it
+fn world() -> ParticleSim {
+    let mut sim = ParticleSim::new_seeded(96, 64, Particle::new(air()).set_temperature(300), 17);
+    for x in 0..96 {
+        sim.set_particle(x, 63, Particle::new(stone()).set_temperature(900));
+    }
+    for x in 40..50 {
+        sim.set_particle(x, 30, Particle::new(stone()).set_temperature(300));
+    }
+    sim.paint(16, 16, 8, Particle::new(sand()).set_temperature(300));
+    sim.paint(50, 48, 10, Particle::new(water()).set_temperature(350));
+    sim.paint(80, 24, 8, Particle::new(oil()).set_temperature(300));
+    sim.set_miscibility(&water(), &oil(), 0.2);
+    sim
+}
+
+fn run(sim: &mut ParticleSim) {
+    for t in 0..80 {
+        tick(sim, t);
+    }
+}
+
+#[test]
+fn parallel_is_the_same_as_serial() {
+    let mut serial = world();
+    run(&mut serial);
+
+    let mut parallel = world();
+    parallel.set_parallel(true);
+    // more threads than this machine might have, so the chunks of a phase really do run interleaved
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    pool.install(|| run(&mut parallel));
+
+    assert!(serial.chunk_stats().active > 0);
+    assert_eq!(bytes(&parallel), bytes(&serial));
+    assert_eq!(parallel.rng().gen::<u64>(), serial.rng().gen::<u64>());
+}
@@ -0,0 +1,103 @@
+use simple_particle_sim::error::SimError;
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+use simple_particle_sim::region::Anchor;
+use simple_particle_sim::replay::Edit;
+
+fn fill() -> Particle {
+    Particle::new(ParticleType::new(0))
+}
+
+// a 4x4 grid where every cell has a type of its own, 1 + x + 4 * y
+fn numbered() -> ParticleSim {
+    let mut sim = ParticleSim::new_seeded(4, 4, fill(), 1);
+    for y in 0..4 {
+        for x in 0..4 {
+            sim.set_particle(x, y, Particle::new(ParticleType::new(1 + x as u32 + 4 * y as u32)));
+        }
+    }
+    sim
+}
+
+fn id(sim: &ParticleSim, x: usize, y: usize) -> u32 {
+    sim.particle_at(x, y).particle_type.id
+}
+
+#[test]
+fn resize_keeps_the_anchor_in_place() {
+    // where the old top left corner ends up when growing to 6x6, and what ends up at the new top left when cropping to 2x2
+    let cases = [
+        (Anchor::TopLeft, (0, 0), 1),
+        (Anchor::Top, (1, 0), 2),
+        (Anchor::TopRight, (2, 0), 3),
+        (Anchor::Left, (0, 1), 5),
+        (Anchor::Center, (1, 1), 6),
+        (Anchor::Right, (2, 1), 7),
+        (Anchor::BottomLeft, (0, 2), 9),
+        (Anchor::Bottom, (1, 2), 10),
+        (Anchor::BottomRight, (2, 2), 11),
+    ];
+    for (anchor, (x, y), top_left) in cases {
+        let mut sim = numbered();
+        sim.resize(6, 6, anchor, fill()).unwrap();
+        assert_eq!((sim.width, sim.height), (6, 6));
+        assert_eq!(id(&sim, x, y), 1, "{:?}", anchor);
+        assert_eq!(id(&sim, x + 3, y + 3), 16, "{:?}", anchor);
+        let filled = (0..6).flat_map(|y| (0..6).map(move |x| (x, y))).filter(|(x, y)| id(&sim, *x, *y) == 0).count();
+        assert_eq!(filled, 36 - 16, "{:?}", anchor);
+
+        let mut sim = numbered();
+        sim.resize(2, 2, anchor, fill()).unwrap();
+        assert_eq!(id(&sim, 0, 0), top_left, "{:?}", anchor);
+        assert_eq!(id(&sim, 1, 1), top_left + 5, "{:?}", anchor);
+    }
+    assert!(matches!(numbered().resize(0, 3, Anchor::Center, fill()), Err(SimError::InvalidSize { width: 0, height: 3 })));
+}
+
+#[test]
+fn sub_region_is_clipped_to_the_grid() {
+    let sim = numbered();
+    let region = sim.sub_region(1, 2, 2, 2).unwrap();
+    assert_eq!((region.width, region.height), (2, 2));
+    assert_eq!([id(&region, 0, 0), id(&region, 1, 0), id(&region, 0, 1), id(&region, 1, 1)], [10, 11, 14, 15]);
+
+    let region = sim.sub_region(2, 3, 10, 10).unwrap();
+    assert_eq!((region.width, region.height), (2, 1));
+    assert_eq!([id(&region, 0, 0), id(&region, 1, 0)], [15, 16]);
+
+    assert!(matches!(sim.sub_region(4, 0, 1, 1), Err(SimError::OutOfBounds { x: 4, y: 0, width: 4, height: 4 })));
+    assert!(matches!(sim.sub_region(0, 7, 1, 1), Err(SimError::OutOfBounds { x: 0, y: 7, .. })));
+    assert!(matches!(sim.sub_region(1, 1, 0, 2), Err(SimError::InvalidSize { width: 0, height: 2 })));
+}
+
+#[test]
+fn paste_region_leaves_out_what_sticks_out() {
+    let region = numbered().sub_region(0, 0, 3, 3).unwrap();
+    let mut sim = ParticleSim::new_seeded(4, 4, fill(), 1);
+    sim.paste_region(&region, 2, 3);
+    for y in 0..4 {
+        for x in 0..4 {
+            let expected = if x >= 2 && y == 3 { 1 + (x as u32 - 2) } else { 0 };
+            assert_eq!(id(&sim, x, y), expected, "({}, {})", x, y);
+        }
+    }
+    // entirely off the grid does nothing at all
+    sim.paste_region(&region, 10, 10);
+    sim.paste_region(&region, usize::MAX - 1, usize::MAX);
+    assert_eq!(id(&sim, 3, 3), 2);
+}
+
+#[test]
+fn a_resize_that_cuts_a_body_records_only_the_resize() {
+    let stone = ParticleType { solid: true, ..ParticleType::new(5) };
+    let mut sim = ParticleSim::new_seeded(8, 8, fill(), 1);
+    for x in 3..7 {
+        sim.set_particle(x, 4, Particle::new(stone));
+    }
+    sim.create_rigid_body(3, 4).unwrap();
+    sim.start_recording();
+    sim.resize(5, 8, Anchor::TopLeft, fill()).unwrap();
+    assert_eq!(sim.rigid_bodies().count(), 0);
+    let replay = sim.stop_recording().unwrap();
+    assert_eq!(replay.events.len(), 1);
+    assert!(matches!(replay.events[0].edit, Edit::Resize { width: 5, height: 8, .. }));
+}
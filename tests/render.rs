@@ -0,0 +1,177 @@
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+use simple_particle_sim::error::SimError;
+use simple_particle_sim::render::{ColorRamp, PixelFormat, RenderMode, Viewport};
+
+fn air() -> ParticleType {
+    ParticleType { liquid_density: 0.001, gas_density: 0.0012, melting_temperature: 10, boiling_temperature: 20, ..ParticleType::new(0) }
+}
+
+fn sand() -> ParticleType {
+    ParticleType { liquid_density: 1.5, gas_density: 1.5, melting_temperature: 1500, boiling_temperature: 3000, ..ParticleType::new(1) }
+}
+
+fn water() -> ParticleType {
+    ParticleType { liquid_density: 1.0, gas_density: 0.0006, melting_temperature: 273, boiling_temperature: 373, ..ParticleType::new(2) }
+}
+
+fn stone() -> ParticleType {
+    ParticleType { solid: true, ..sand() }
+}
+
+#[test]
+fn heatmap_follows_the_ramp() {
+    let ramp = ColorRamp::new(vec![[0, 0, 255], [255, 0, 0]]);
+    assert_eq!(ramp.sample(0.5), [128, 0, 128]);
+    assert_eq!((ramp.sample(-1.0), ramp.sample(2.0)), ([0, 0, 255], [255, 0, 0]));
+
+    let mut sim = ParticleSim::new_seeded(4, 1, Particle::new(stone()).set_temperature(100), 1);
+    sim.set_particle(1, 0, Particle::new(stone()).set_temperature(200));
+    sim.set_particle(2, 0, Particle::new(stone()).set_temperature(300));
+    sim.set_particle(3, 0, Particle::new(stone()).set_temperature(900));
+    let pixels = sim.render_pixels_with(&RenderMode::Heatmap { min_temperature: 100.0, max_temperature: 300.0, ramp });
+    assert_eq!(pixels, vec![[0, 0, 255], [128, 0, 128], [255, 0, 0], [255, 0, 0]]);
+}
+
+#[test]
+fn phase_and_density_tell_the_states_apart() {
+    let mut sim = ParticleSim::new_seeded(4, 1, Particle::new(air()).set_temperature(300), 1);
+    sim.set_particle(0, 0, Particle::new(stone()).set_temperature(300));
+    sim.set_particle(1, 0, Particle::new(sand()).set_temperature(300));
+    sim.set_particle(2, 0, Particle::new(water()).set_temperature(300));
+
+    let phases = sim.render_pixels_with(&RenderMode::Phase);
+    for (i, a) in phases.iter().enumerate() {
+        for b in &phases[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+    // greyscale, and the heavier the brighter
+    let density = sim.render_pixels_with(&RenderMode::Density);
+    assert!(density.iter().all(|[r, g, b]| r == g && g == b));
+    assert!(density[1][0] > density[2][0] && density[2][0] > density[3][0] && density[3][0] > 0);
+}
+
+#[test]
+fn material_is_what_render_pixels_draws() {
+    let mut sim = ParticleSim::new_seeded(8, 8, Particle::new(air()).set_temperature(300), 1);
+    sim.paint(4, 4, 2, Particle::new(water()).set_temperature(300));
+    sim.set_particle(1, 1, Particle::new(stone()).set_temperature(1400));
+    assert_eq!(sim.render_pixels_with(&RenderMode::Material), sim.render_pixels());
+}
+
+#[test]
+fn active_chunks_dims_the_ones_asleep() {
+    let mut sim = ParticleSim::new_seeded(64, 32, Particle::new(stone()).set_temperature(300), 1);
+    for t in 0..5 {
+        sim.simulate_movement(t);
+    }
+    sim.set_particle(50, 10, Particle::new(sand()).set_temperature(300));
+    assert!(!sim.is_chunk_active(0, 0) && sim.is_chunk_active(1, 0));
+    let material = sim.render_pixels();
+    let pixels = sim.render_pixels_with(&RenderMode::ActiveChunks);
+    // chunk borders are marked, asleep is a third as bright, awake is as is
+    assert_eq!((pixels[0], pixels[32], pixels[32 + 5 * 64]), ([255, 0, 255], [255, 0, 255], [255, 0, 255]));
+    assert_eq!(pixels[5 + 5 * 64], material[5 + 5 * 64].map(|c| c / 3));
+    assert_eq!(pixels[50 + 5 * 64], material[50 + 5 * 64]);
+}
+
+#[test]
+fn velocity_shows_what_moved() {
+    let mut sim = ParticleSim::new_seeded(8, 8, Particle::new(air()).set_temperature(300), 1);
+    sim.set_particle(4, 0, Particle::new(sand()).set_temperature(300));
+    sim.simulate_movement(0);
+    let pixels = sim.render_pixels_with(&RenderMode::Velocity { max_speed: 1.0 });
+    assert_eq!(pixels[4 + 8], [255, 255, 255]);
+    assert_eq!(pixels[7 * 8], [0, 0, 0]);
+}
+
+// a 3x2 grid with a different colour in every cell, red going up with x and green with y
+fn colored() -> ParticleSim {
+    let mut sim = ParticleSim::new_seeded(3, 2, Particle::new(stone()).set_temperature(300), 1);
+    for y in 0..2 {
+        for x in 0..3 {
+            let color = [50 + 50 * x as u8, 100 + 100 * y as u8, 7, 255];
+            sim.set_particle(x, y, Particle::new(ParticleType { id: 10 + x as u32 + 3 * y as u32, solid_color: color, ..stone() }));
+        }
+    }
+    sim
+}
+
+#[test]
+fn render_into_scales_and_clips_the_viewport() {
+    let mut sim = colored();
+    let rgb = sim.render_pixels();
+    // one cell hanging off the top left, scaled up 2x
+    let viewport = Viewport { x: -1, y: -1, width: 3, height: 2, scale: 2 };
+    assert_eq!(viewport.pixel_size().unwrap(), (6, 4));
+    let mut buffer = vec![9; 6 * 4 * 4 + 3];
+    sim.render_into(&mut buffer, PixelFormat::Rgba8, viewport, &RenderMode::Material).unwrap();
+    for py in 0..4 {
+        for px in 0..6 {
+            let (x, y) = (px as i64 / 2 - 1, py as i64 / 2 - 1);
+            let expected = match (x, y) {
+                (0.., 0..) => {
+                    let [r, g, b] = rgb[x as usize + y as usize * 3];
+                    [r, g, b, 255]
+                }
+                _ => [0, 0, 0, 0],
+            };
+            assert_eq!(buffer[(px + py * 6) * 4..][..4], expected, "pixel ({}, {})", px, py);
+        }
+    }
+    // past what the viewport needs is left alone
+    assert_eq!(buffer[6 * 4 * 4..], [9, 9, 9]);
+}
+
+#[test]
+fn bgra_swaps_red_and_blue() {
+    let sim = colored();
+    let viewport = Viewport::whole(&sim, 1);
+    let (mut rgba, mut bgra) = (vec![0; 3 * 2 * 4], vec![0; 3 * 2 * 4]);
+    sim.render_into(&mut rgba, PixelFormat::Rgba8, viewport, &RenderMode::Material).unwrap();
+    sim.render_into(&mut bgra, PixelFormat::Bgra8, viewport, &RenderMode::Material).unwrap();
+    for (a, b) in rgba.chunks_exact(4).zip(bgra.chunks_exact(4)) {
+        assert_eq!([a[2], a[1], a[0], a[3]], b);
+    }
+}
+
+#[test]
+fn render_into_checks_the_buffer_and_scale() {
+    let sim = colored();
+    let mut buffer = vec![0; 3 * 2 * 4 * 4 - 1];
+    let result = sim.render_into(&mut buffer, PixelFormat::Rgba8, Viewport::whole(&sim, 2), &RenderMode::Material);
+    assert!(matches!(result, Err(SimError::BufferTooSmall { needed: 96, len: 95 })));
+    let result = sim.render_into(&mut buffer, PixelFormat::Rgba8, Viewport::whole(&sim, 0), &RenderMode::Material);
+    assert!(matches!(result, Err(SimError::InvalidSize { .. })));
+    // sizes whose byte count doesn't fit in a usize, rather than wrapping around to something that passes the check
+    for viewport in [Viewport::whole(&sim, usize::MAX), Viewport { x: 0, y: 0, width: usize::MAX / 2, height: 3, scale: 1 }] {
+        let result = sim.render_into(&mut buffer, PixelFormat::Rgba8, viewport, &RenderMode::Material);
+        assert!(matches!(result, Err(SimError::InvalidSize { .. })), "{:?}", viewport);
+    }
+}
+
+#[test]
+fn particle_color_matches_the_render() {
+    let steam = ParticleType { vapor_color: [220, 220, 230, 80], ..water() };
+    let mut sim = ParticleSim::new_seeded(2, 1, Particle::new(steam).set_temperature(400), 1);
+    sim.set_particle(1, 0, Particle::new(stone()).set_temperature(1400));
+    sim.set_background([30, 60, 200, 255]);
+    let pixels = sim.render_pixels();
+    assert_eq!(sim.get_particle_color(0, 0), pixels[0]);
+    assert_eq!(sim.get_particle_color(1, 0), pixels[1]);
+}
+
+#[test]
+fn translucent_vapour_blends_with_the_background() {
+    // a fifth red over blue
+    let vapour = ParticleType { vapor_color: [255, 0, 0, 51], ..water() };
+    let mut sim = ParticleSim::new_seeded(1, 1, Particle::new(vapour).set_temperature(400), 1);
+    sim.set_background([0, 0, 255, 255]);
+    assert_eq!(sim.get_particle_color(0, 0), [51, 0, 204]);
+
+    // nothing to blend with, so it comes out as the particle's own colour and alpha
+    sim.set_background([0, 0, 0, 0]);
+    let mut buffer = [0; 4];
+    sim.render_into(&mut buffer, PixelFormat::Rgba8, Viewport::whole(&sim, 1), &RenderMode::Material).unwrap();
+    assert_eq!(buffer, [255, 0, 0, 51]);
+}
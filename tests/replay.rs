@@ -0,0 +1,189 @@
+mod common;
+
+use common::{air, bytes, oil, sand, stone, water, PASSES};
+use simple_particle_sim::boundary::{Boundary, Edge};
+use simple_particle_sim::error::SimError;
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+use simple_particle_sim::region::Anchor;
+use simple_particle_sim::replay::{Edit, Replay, ReplayEvent};
+
+fn world() -> ParticleSim {
+    let mut sim = ParticleSim::new_seeded(32, 32, Particle::new(air()).set_temperature(300), 11);
+    for x in 4..28 {
+        sim.set_particle(x, 31, Particle::new(stone()).set_temperature(300));
+    }
+    sim.paint(10, 10, 4, Particle::new(sand()).set_temperature(300));
+    sim.paint(22, 12, 4, Particle::new(water()).set_temperature(350));
+    sim
+}
+
+// runs every pass for the given ticks, handing (sim, tick, pass) to edit before each one
+fn run(sim: &mut ParticleSim, ticks: std::ops::Range<u64>, mut edit: impl FnMut(&mut ParticleSim, u64, usize)) {
+    for t in ticks {
+        for (i, pass) in PASSES.iter().enumerate() {
+            edit(sim, t, i);
+            pass(sim, t);
+        }
+    }
+}
+
+// plays the replay back over the same ticks and checks it ends up exactly where the recording did
+fn check_replay(replay: Replay, ticks: std::ops::Range<u64>, recorded: &ParticleSim) {
+    let mut bytes_of_replay = Vec::new();
+    replay.write_to(&mut bytes_of_replay).unwrap();
+    let replay = Replay::read_from(&mut bytes_of_replay.as_slice()).unwrap();
+
+    let (mut sim, mut player) = replay.play();
+    run(&mut sim, ticks, |sim, _, _| player.apply_edits(sim).unwrap());
+    player.apply_edits(&mut sim).unwrap();
+    assert!(player.finished());
+    assert_eq!(bytes(&sim), bytes(recorded));
+}
+
+#[test]
+fn edits_between_passes_replay_between_the_same_passes() {
+    let mut sim = world();
+    run(&mut sim, 0..5, |_, _, _| {});
+    sim.start_recording();
+    run(&mut sim, 5..40, |sim, t, pass| match (t, pass) {
+        (8, 1) => sim.paint(16, 4, 3, Particle::new(water()).set_temperature(300)),
+        (8, 3) => sim.set_particle(5, 5, Particle::new(sand()).set_temperature(900)),
+        (12, 2) => sim.swap_particles(16, 4, 5, 5),
+        (20, 1) => sim.set_particle_energy(16, 20, 0),
+        _ => {}
+    });
+    sim.set_particle(1, 1, Particle::new(sand()));
+    let replay = sim.stop_recording().unwrap();
+    assert_eq!(replay.first_tick(), 5);
+    check_replay(replay, 5..40, &sim);
+}
+
+#[test]
+fn every_setting_is_recorded() {
+    let mut sim = world();
+    sim.start_recording();
+    run(&mut sim, 0..60, |sim, t, pass| match (t, pass) {
+        (2, 0) => {
+            sim.create_rigid_body(10, 31);
+        }
+        (4, 1) => sim.set_boundary(Edge::Left, Boundary::Wrap),
+        (6, 2) => sim.set_boundary(Edge::Top, Boundary::Open(Particle::new(oil()).set_temperature(300))),
+        (10, 3) => sim.set_miscibility(&water(), &oil(), 0.5),
+        (12, 0) => sim.set_ambient_temperature(250),
+        (12, 1) => sim.set_cooling_rate(&water(), 0.1),
+        (20, 2) => sim.reseed(1234),
+        (30, 0) => {
+            let id = sim.rigid_bodies().next().unwrap().id;
+            sim.break_rigid_body(id);
+        }
+        _ => {}
+    });
+    let replay = sim.stop_recording().unwrap();
+    assert_eq!(replay.events.len(), 8);
+    check_replay(replay, 0..60, &sim);
+}
+
+#[test]
+fn restoring_while_recording_replays() {
+    let mut sim = world();
+    sim.set_history_capacity(8);
+    sim.start_recording();
+    run(&mut sim, 0..30, |sim, t, pass| {
+        if pass == 0 {
+            sim.push_snapshot();
+        }
+        if (t, pass) == (20, 2) {
+            assert!(sim.rewind(3));
+        }
+        if (t, pass) == (25, 1) {
+            let snapshot = sim.snapshot();
+            sim.paint(16, 16, 5, Particle::new(sand()));
+            sim.restore(&snapshot);
+        }
+    });
+    let replay = sim.stop_recording().unwrap();
+    check_replay(replay, 0..30, &sim);
+}
+
+#[test]
+fn nothing_is_recorded_unless_recording() {
+    let mut sim = world();
+    sim.set_ambient_temperature(200);
+    sim.start_recording();
+    let replay = sim.stop_recording().unwrap();
+    assert!(replay.events.is_empty());
+    assert_eq!(replay.start().ambient_temperature(), 200);
+}
+
+#[test]
+fn broken_replays_are_errors() {
+    let mut sim = world();
+    sim.start_recording();
+    run(&mut sim, 0..3, |sim, t, pass| {
+        if pass == 1 {
+            sim.set_particle(t as usize, 2, Particle::new(oil()).set_temperature(300));
+        }
+    });
+    let mut bytes = Vec::new();
+    sim.stop_recording().unwrap().write_to(&mut bytes).unwrap();
+
+    for len in 0..bytes.len() {
+        assert!(Replay::read_from(&mut &bytes[..len]).is_err(), "{} of {} bytes", len, bytes.len());
+    }
+    // the start state's length comes right after the magic and the version, it shouldn't be taken at its word
+    bytes[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(Replay::read_from(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn a_replayed_resize_is_bounded_like_a_save() {
+    let mut sim = world();
+    sim.start_recording();
+    sim.resize(37, 41, Anchor::Center, Particle::new(air())).unwrap();
+    let mut bytes = Vec::new();
+    sim.stop_recording().unwrap().write_to(&mut bytes).unwrap();
+
+    let size: Vec<u8> = [37u32.to_le_bytes(), 41u32.to_le_bytes()].concat();
+    let at = bytes.windows(8).rposition(|window| window == size.as_slice()).unwrap();
+    bytes[at..at + 8].copy_from_slice(&[0xff; 8]);
+    assert!(matches!(Replay::read_from(&mut bytes.as_slice()), Err(SimError::Format(_))));
+}
+
+#[test]
+fn a_failing_edit_doesnt_hold_back_the_rest() {
+    let mut sim = world();
+    sim.start_recording();
+    sim.set_particle(3, 3, Particle::new(oil()).set_temperature(300));
+    sim.set_particle(4, 3, Particle::new(oil()).set_temperature(300));
+    let mut replay = sim.stop_recording().unwrap();
+    // a resize that can't be done, put in by hand between the two
+    let broken = Edit::Resize { width: 0, height: 5, anchor: Anchor::Center, fill: Particle::new(air()) };
+    replay.events.insert(1, ReplayEvent { after_pass: 0, edit: broken });
+
+    let (mut sim, mut player) = replay.play();
+    assert!(matches!(player.apply_edits(&mut sim), Err(SimError::InvalidSize { width: 0, height: 5 })));
+    assert!(player.finished());
+    assert_eq!(sim.particle_at(4, 3).particle_type.id, oil().id);
+}
+
+#[test]
+fn replayed_materials_and_rates_are_checked() {
+    let mut sim = world();
+    sim.start_recording();
+    sim.set_cooling_rate(&water(), 5.0);
+    let mut bytes = Vec::new();
+    sim.stop_recording().unwrap().write_to(&mut bytes).unwrap();
+    // the setter clamped it before recording it
+    assert_eq!(bytes[bytes.len() - 4..], 1.0f32.to_le_bytes());
+    assert!(Replay::read_from(&mut bytes.as_slice()).is_ok());
+    let at = bytes.len() - 4;
+    bytes[at..].copy_from_slice(&f32::NAN.to_le_bytes());
+    assert!(matches!(Replay::read_from(&mut bytes.as_slice()), Err(SimError::Format(_))));
+
+    let mut sim = world();
+    sim.start_recording();
+    sim.set_particle(3, 3, Particle::new(ParticleType { heat_capacity: 0, ..oil() }));
+    let mut bytes = Vec::new();
+    sim.stop_recording().unwrap().write_to(&mut bytes).unwrap();
+    assert!(matches!(Replay::read_from(&mut bytes.as_slice()), Err(SimError::Format(_))));
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{air, oil, stone, tick, water};
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+use simple_particle_sim::rigid_body::RigidBody;
+
+// a bit lighter than water but heavier than oil
+fn wood() -> ParticleType {
+    ParticleType { id: 20, rigid: true, gas_density: 0.95, ..stone() }
+}
+
+fn block(sim: &mut ParticleSim, particle_type: ParticleType, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>) {
+    for y in ys {
+        for x in xs.clone() {
+            sim.set_particle(x, y, Particle::new(particle_type).set_temperature(300));
+        }
+    }
+}
+
+fn world() -> ParticleSim {
+    ParticleSim::new_seeded(40, 60, Particle::new(air()).set_temperature(300), 9)
+}
+
+fn run(sim: &mut ParticleSim, ticks: u64) {
+    for t in 0..ticks {
+        tick(sim, t);
+    }
+}
+
+fn bodies(sim: &ParticleSim) -> Vec<&RigidBody> {
+    sim.rigid_bodies().collect()
+}
+
+// lowest and highest row the body's cells are in
+fn rows(sim: &ParticleSim, body: &RigidBody) -> (usize, usize) {
+    let ys: Vec<usize> = (0..sim.height)
+        .flat_map(|y| (0..sim.width).map(move |x| (x, y)))
+        .filter(|(x, y)| sim.particle_at(*x, *y).get_body() == body.id)
+        .map(|(_, y)| y)
+        .collect();
+    (*ys.iter().min().unwrap(), *ys.iter().max().unwrap())
+}
+
+#[test]
+fn a_body_falls_and_lands_on_stone() {
+    let mut sim = world();
+    block(&mut sim, stone(), 0..40, 50..60);
+    block(&mut sim, ParticleType { rigid: true, ..stone() }, 18..22, 5..9);
+    run(&mut sim, 60);
+    let bodies = bodies(&sim);
+    assert_eq!(bodies.len(), 1);
+    assert_eq!(rows(&sim, bodies[0]), (46, 49));
+    assert_eq!(bodies[0].velocity, [0.0, 0.0]);
+    assert_eq!(bodies[0].angle, 0.0);
+}
+
+#[test]
+fn wood_floats_on_water_and_sinks_in_oil() {
+    for liquid in [water(), oil()] {
+        let mut sim = world();
+        block(&mut sim, liquid, 0..40, 30..60);
+        block(&mut sim, wood(), 18..22, 20..24);
+        run(&mut sim, 200);
+        let bodies = bodies(&sim);
+        assert_eq!(bodies.len(), 1);
+        let (top, bottom) = rows(&sim, bodies[0]);
+        if liquid.id == water().id {
+            // just about all of it under, but up at the surface rather than anywhere near the bottom
+            assert!(top <= 30 && bottom < 40, "{:?} in water", (top, bottom));
+        } else {
+            assert_eq!(bottom, 59, "on the bottom of the oil");
+        }
+    }
+}
+
+#[test]
+fn a_body_hanging_over_an_edge_tips_off_it() {
+    // a ledge against the left wall, with a bar on it that's either held up under its middle or hanging off
+    for (bar, tips) in [(4..16, false), (14..26, true)] {
+        let mut sim = world();
+        block(&mut sim, stone(), 0..20, 40..60);
+        block(&mut sim, ParticleType { rigid: true, ..stone() }, bar.clone(), 38..40);
+        let mut tipped = false;
+        for t in 0..80 {
+            tick(&mut sim, t);
+            tipped |= sim.rigid_bodies().any(|body| body.angle != 0.0);
+        }
+        assert_eq!(tipped, tips, "bar at {:?}", bar);
+        // a bar that tipped ends up off the ledge, down on the floor
+        let bottom = sim.rigid_bodies().map(|body| rows(&sim, body).1).max().unwrap();
+        assert_eq!(bottom == 59, tips, "bar at {:?}", bar);
+    }
+}
+
+#[test]
+fn a_hard_landing_cracks_a_weak_body() {
+    // 16 cells, a strength of 16 cracks landing at a cell per tick and 100 needs over 6
+    for (strength, pieces) in [(16, 2), (100, 1)] {
+        let mut sim = world();
+        block(&mut sim, ParticleType { rigid: true, strength, ..stone() }, 18..22, 20..24);
+        run(&mut sim, 60);
+        assert_eq!(bodies(&sim).len(), pieces, "strength {}", strength);
+        let cells = (0..sim.height).flat_map(|y| (0..sim.width).map(move |x| (x, y))).filter(|(x, y)| sim.particle_at(*x, *y).get_body() != 0).count();
+        assert_eq!(cells, 16);
+    }
+}
+
+#[test]
+fn losing_cells_or_breaking_a_body_splits_it() {
+    let mut sim = world();
+    block(&mut sim, stone(), 0..40, 50..60);
+    // a dumbbell, two blocks with a thin bar between them
+    let rigid = ParticleType { rigid: true, ..stone() };
+    block(&mut sim, rigid, 10..14, 44..50);
+    block(&mut sim, rigid, 14..26, 46..47);
+    block(&mut sim, rigid, 26..30, 44..50);
+    run(&mut sim, 3);
+    assert_eq!(bodies(&sim).len(), 1);
+
+    // losing the bar, the way melting it would, leaves two bodies
+    block(&mut sim, water(), 14..26, 46..47);
+    run(&mut sim, 3);
+    assert_eq!(bodies(&sim).len(), 2);
+
+    let id = bodies(&sim)[0].id;
+    sim.break_rigid_body(id);
+    assert_eq!(bodies(&sim).len(), 1);
+}
@@ -0,0 +1,111 @@
+use simple_particle_sim::error::SimError;
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+fn round_trip(sim: &ParticleSim) -> ParticleSim {
+    let mut bytes = Vec::new();
+    sim.write_to(&mut bytes).unwrap();
+    ParticleSim::read_from(&mut bytes.as_slice()).unwrap()
+}
+
+#[test]
+fn types_sharing_an_id_stay_apart() {
+    let sand = ParticleType { solid_color: [200, 180, 90, 255], ..ParticleType::new(5) };
+    let red_sand = ParticleType { solid_color: [200, 60, 40, 255], liquid_density: 2.0, ..ParticleType::new(5) };
+    let mut sim = ParticleSim::new_seeded(8, 8, Particle::new(ParticleType::new(0)), 3);
+    for x in 0..8 {
+        sim.set_particle(x, 6, Particle::new(sand).set_temperature(300));
+        sim.set_particle(x, 7, Particle::new(red_sand).set_temperature(400));
+    }
+
+    let loaded = round_trip(&sim);
+    for y in 0..8 {
+        for x in 0..8 {
+            assert_eq!(loaded.particle_at(x, y), sim.particle_at(x, y), "({}, {})", x, y);
+        }
+    }
+    assert_eq!(loaded.particles.types().len(), 3);
+}
+
+#[test]
+fn types_no_cell_uses_are_left_out() {
+    let mut sim = ParticleSim::new_seeded(8, 8, Particle::new(ParticleType::new(0)), 3);
+    for id in 1..10 {
+        sim.set_particle(2, 2, Particle::new(ParticleType::new(id)));
+    }
+    assert_eq!(round_trip(&sim).particles.types().len(), 2);
+}
+
+// an 8x8 simulation with a rigid body and a bit of everything in the settings
+fn busy() -> ParticleSim {
+    let stone = ParticleType { solid: true, melting_temperature: 1500, boiling_temperature: 3000, ..ParticleType::new(1) };
+    let mut sim = ParticleSim::new_seeded(8, 8, Particle::new(ParticleType::new(0)).set_temperature(300), 3);
+    sim.set_particle(3, 3, Particle::new(stone).set_temperature(300));
+    sim.set_particle(4, 3, Particle::new(stone).set_temperature(300));
+    sim.create_rigid_body(3, 3).unwrap();
+    sim.set_miscibility(&ParticleType::new(0), &stone, 0.5);
+    sim.set_cooling_rate(&stone, 0.1);
+    sim
+}
+
+#[test]
+fn a_huge_size_is_refused_before_anything_is_allocated() {
+    let mut bytes = Vec::new();
+    busy().write_to(&mut bytes).unwrap();
+    // width and height come right after the magic and the version
+    bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    bytes[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(ParticleSim::read_from(&mut bytes.as_slice()), Err(SimError::Format(_))));
+}
+
+#[test]
+fn cut_off_files_are_errors() {
+    let mut bytes = Vec::new();
+    busy().write_to(&mut bytes).unwrap();
+    for len in 0..bytes.len() {
+        assert!(ParticleSim::read_from(&mut &bytes[..len]).is_err(), "{} of {} bytes", len, bytes.len());
+    }
+}
+
+#[test]
+fn broken_bytes_dont_panic() {
+    let mut bytes = Vec::new();
+    busy().write_to(&mut bytes).unwrap();
+    // past the header, a broken size there is what the test above is for and a merely big one would take a while
+    for i in 41..bytes.len() {
+        let mut broken = bytes.clone();
+        broken[i] ^= 0xff;
+        let _ = ParticleSim::read_from(&mut broken.as_slice());
+    }
+}
+
+#[test]
+fn materials_and_rates_that_the_setters_turn_away_dont_load() {
+    let sand = ParticleType { liquid_density: 1.5, gas_density: 1.5, melting_temperature: 1500, boiling_temperature: 3000, ..ParticleType::new(1) };
+    let mut sim = ParticleSim::new_seeded(8, 8, Particle::new(ParticleType::new(0)), 3);
+    sim.set_particle(2, 2, Particle::new(sand).set_temperature(300));
+    sim.set_cooling_rate(&sand, 0.25);
+    sim.set_miscibility(&sand, &ParticleType::new(0), 0.75);
+    let mut bytes = Vec::new();
+    sim.write_to(&mut bytes).unwrap();
+    assert!(ParticleSim::read_from(&mut bytes.as_slice()).is_ok());
+
+    // rates are only ever stored in (0, 1]
+    for (rate, bad) in [(0.25f32, 2.0f32), (0.25, f32::NAN), (0.75, -1.0), (0.75, 0.0)] {
+        let mut broken = bytes.clone();
+        let at = broken.windows(4).rposition(|window| window == rate.to_le_bytes()).unwrap();
+        broken[at..at + 4].copy_from_slice(&bad.to_le_bytes());
+        let result = ParticleSim::read_from(&mut broken.as_slice());
+        assert!(matches!(result, Err(SimError::Format(_))), "{} in place of {}", bad, rate);
+    }
+
+    // a material the plain functions put in saves fine, but doesn't come back out
+    let weightless = ParticleType { heat_capacity: 0, ..ParticleType::new(7) };
+    sim.set_particle(3, 3, Particle::new(weightless));
+    let mut bytes = Vec::new();
+    sim.write_to(&mut bytes).unwrap();
+    assert!(matches!(ParticleSim::read_from(&mut bytes.as_slice()), Err(SimError::Format(_))));
+    // though a snapshot of it still restores
+    let snapshot = sim.snapshot();
+    sim.restore(&snapshot);
+    assert_eq!(sim.particle_at(3, 3).particle_type.heat_capacity, 0);
+}
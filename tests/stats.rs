@@ -0,0 +1,61 @@
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+fn air() -> ParticleType {
+    ParticleType { liquid_density: 0.001, gas_density: 0.0012, melting_temperature: 10, boiling_temperature: 20, ..ParticleType::new(0) }
+}
+
+fn water() -> ParticleType {
+    ParticleType { liquid_density: 1.0, gas_density: 0.0006, melting_temperature: 273, boiling_temperature: 373, ..ParticleType::new(1) }
+}
+
+// 6x4 of air at 300 K with a 2x2 block of water at 350 K in the top left and one of ice at 250 K next to it
+fn sim() -> ParticleSim {
+    let mut sim = ParticleSim::new_seeded(6, 4, Particle::new(air()).set_temperature(300), 2);
+    for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        sim.set_particle(x, y, Particle::new(water()).set_temperature(350));
+        sim.set_particle(x + 2, y, Particle::new(ParticleType { solid: true, ..water() }).set_temperature(250));
+    }
+    sim
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.01
+}
+
+#[test]
+fn fields_are_laid_out_like_the_grid() {
+    let sim = sim();
+    let field = sim.temperature_field();
+    assert_eq!(field.len(), 24);
+    assert!(close(field[1 + 6], 350.0) && close(field[3 + 6], 250.0) && close(field[5 + 3 * 6], 300.0));
+    assert!(close(sim.density_field()[0], 1.0));
+
+    // clipped to the grid, so 2x2 out of the 3x3 asked for
+    let corner = sim.temperature_field_in(4, 2, 3, 3);
+    assert_eq!(corner.len(), 4);
+    assert!(corner.iter().all(|temperature| close(*temperature, 300.0)));
+    assert_eq!(sim.density_field_in(1, 1, 2, 1).len(), 2);
+    assert!(sim.temperature_field_in(6, 0, 2, 2).is_empty());
+}
+
+#[test]
+fn stats_add_up() {
+    let sim = sim();
+    let stats = sim.stats();
+    assert_eq!(stats.cells, 24);
+    assert!(close(stats.min_temperature, 250.0) && close(stats.max_temperature, 350.0));
+    assert!(close(stats.mean_temperature, (4.0 * 350.0 + 4.0 * 250.0 + 16.0 * 300.0) / 24.0));
+    assert_eq!(stats.total_energy, sim.total_energy());
+    assert_eq!(stats.total_energy, (0..4).flat_map(|y| (0..6).map(move |x| (x, y))).map(|(x, y)| sim.particle_at(x, y).energy as u128).sum());
+    assert_eq!((stats.materials[&0], stats.materials[&1]), (16, 8));
+    assert_eq!(stats.phases, [4, 0, 4, 16]);
+
+    let block = sim.stats_in(1, 0, 2, 10);
+    assert_eq!(block.cells, 8);
+    assert_eq!(block.phases, [2, 0, 2, 4]);
+    assert!(close(block.mean_temperature, 300.0));
+
+    let empty = sim.stats_in(10, 10, 5, 5);
+    assert_eq!((empty.cells, empty.total_energy, empty.min_temperature, empty.mean_temperature), (0, 0, 0.0, 0.0));
+    assert!(empty.materials.is_empty());
+}
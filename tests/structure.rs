@@ -0,0 +1,96 @@
+mod common;
+
+use common::{air, stone, tick};
+use simple_particle_sim::boundary::{Boundary, Edge};
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+fn world() -> ParticleSim {
+    ParticleSim::new_seeded(48, 40, Particle::new(air()).set_temperature(300), 5)
+}
+
+fn slab(sim: &mut ParticleSim, particle_type: ParticleType, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>) {
+    for y in ys {
+        for x in xs.clone() {
+            sim.set_particle(x, y, Particle::new(particle_type).set_temperature(300));
+        }
+    }
+}
+
+// (static solids, solids in a body, powder) across the whole grid
+fn census(sim: &ParticleSim) -> (usize, usize, usize) {
+    let (mut fixed, mut body, mut powder) = (0, 0, 0);
+    for y in 0..sim.height {
+        for x in 0..sim.width {
+            let particle = sim.particle_at(x, y);
+            if particle.particle_type.id == air().id {
+                continue
+            }
+            match (particle.get_state(), particle.get_body()) {
+                (0, 0) => fixed += 1,
+                (0, _) => body += 1,
+                (1, _) => powder += 1,
+                _ => {}
+            }
+        }
+    }
+    (fixed, body, powder)
+}
+
+#[test]
+fn an_anchored_overhang_stands() {
+    let mut sim = world();
+    // a post in mid air with an arm sticking out of its top, nowhere near an edge
+    slab(&mut sim, ParticleType { anchored: true, ..stone() }, 10..12, 10..20);
+    slab(&mut sim, stone(), 12..30, 10..12);
+    let before = census(&sim);
+    for t in 0..20 {
+        tick(&mut sim, t);
+    }
+    assert_eq!(census(&sim), before);
+    assert_eq!(sim.particle_at(29, 11).particle_type.id, stone().id);
+}
+
+#[test]
+fn an_unanchored_slab_falls_as_a_body_or_crumbles_by_strength() {
+    // 36 cells, a body as long as the material is at least that strong
+    for (strength, expected) in [(100, (0, 36, 0)), (36, (0, 36, 0)), (35, (0, 0, 36)), (5, (0, 0, 36))] {
+        let mut sim = world();
+        slab(&mut sim, ParticleType { strength, ..stone() }, 10..22, 10..13);
+        sim.simulate_structure(0);
+        assert_eq!(census(&sim), expected, "strength {}", strength);
+        assert_eq!(sim.rigid_bodies().count(), (expected.1 > 0) as usize);
+    }
+}
+
+#[test]
+fn only_a_wall_holds_things_up() {
+    for boundary in [Boundary::Wall, Boundary::Void(Particle::new(air()).set_temperature(300)), Boundary::Wrap] {
+        let mut sim = world();
+        sim.set_boundary(Edge::Bottom, boundary);
+        // lying on the floor, clear of the side walls
+        slab(&mut sim, stone(), 10..22, 37..40);
+        sim.simulate_structure(0);
+        let held_up = matches!(boundary, Boundary::Wall);
+        assert_eq!(census(&sim), if held_up { (36, 0, 0) } else { (0, 36, 0) }, "{:?}", boundary);
+    }
+}
+
+#[test]
+fn taking_a_support_away_in_a_settled_world_drops_what_it_held() {
+    let mut sim = world();
+    // a post standing on the floor holding up an arm
+    slab(&mut sim, stone(), 10..12, 20..40);
+    slab(&mut sim, stone(), 12..30, 20..22);
+    let mut t = 0;
+    while sim.chunk_stats().active > 0 {
+        tick(&mut sim, t);
+        t += 1;
+    }
+    assert_eq!(census(&sim), (76, 0, 0));
+
+    // cutting the post leaves the top of it and the arm hanging
+    slab(&mut sim, air(), 10..12, 30..31);
+    tick(&mut sim, t);
+    assert_eq!(sim.rigid_bodies().count(), 1);
+    assert_eq!(census(&sim), (18, 56, 0));
+}
@@ -0,0 +1,127 @@
+use simple_particle_sim::boundary::{Boundary, Edge};
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+use simple_particle_sim::region::Anchor;
+use simple_particle_sim::texture::{Pattern, Texture};
+
+fn particle_type(id: u32, texture: Option<Texture>) -> ParticleType {
+    ParticleType {
+        solid: true,
+        liquid_density: 2.0,
+        gas_density: 2.0,
+        heat_capacity: 800,
+        anchored: true,
+        texture,
+        ..ParticleType::new(id)
+    }
+}
+
+fn all_patterns() -> Vec<Pattern> {
+    vec![
+        Pattern::Random,
+        Pattern::Metal { size: 6 },
+        Pattern::ValueNoise { scale: 5.0 },
+        Pattern::Perlin { scale: 8.0, octaves: 4 },
+        Pattern::Wood { ring_spacing: 4.0, grain: 1.5 },
+        Pattern::Brick { width: 8, height: 4, mortar: 1 },
+        Pattern::Crystal { size: 6.0 },
+        Pattern::Speckle { density: 0.1 },
+    ]
+}
+
+#[test]
+fn patterns_stay_in_range_and_repeat() {
+    for pattern in all_patterns() {
+        let texture = Texture::new(pattern, 40, 7);
+        let mut distinct = std::collections::HashSet::new();
+        for y in -20..20 {
+            for x in -20..20 {
+                let value = texture.value(x, y);
+                assert!((0.0..=1.0).contains(&value), "{:?} gave {} at ({}, {})", pattern, value, x, y);
+                let noise = texture.sample(x, y);
+                assert!((88..=168).contains(&noise), "{:?} went past its strength with {}", pattern, noise);
+                assert_eq!(noise, Texture::new(pattern, 40, 7).sample(x, y));
+                distinct.insert(noise);
+            }
+        }
+        assert!(distinct.len() > 1, "{:?} came out flat", pattern);
+    }
+}
+
+#[test]
+fn seed_changes_the_pattern() {
+    for pattern in all_patterns().into_iter().filter(|p| !matches!(p, Pattern::Metal { .. })) {
+        let a = Texture::new(pattern, 60, 1);
+        let b = Texture::new(pattern, 60, 2);
+        let differs = (0..32).flat_map(|y| (0..32).map(move |x| (x, y))).any(|(x, y)| a.sample(x, y) != b.sample(x, y));
+        assert!(differs, "{:?} ignores its seed", pattern);
+    }
+}
+
+#[test]
+fn brick_has_mortar_lines() {
+    let texture = Texture::new(Pattern::Brick { width: 8, height: 4, mortar: 1 }, 100, 3);
+    for x in 0..32 {
+        assert_eq!(texture.value(x, 0), 0.0);
+        assert_eq!(texture.value(x, 4), 0.0);
+    }
+    assert_eq!(texture.value(0, 1), 0.0);
+    assert_eq!(texture.value(4, 5), 0.0); // the next row is shifted half a brick
+    assert!(texture.value(2, 2) > 0.0);
+}
+
+#[test]
+fn spawned_particles_get_the_texture() {
+    let texture = Texture::new(Pattern::Crystal { size: 4.0 }, 50, 11);
+    let stone = Particle::new(particle_type(1, Some(texture)));
+    let air = Particle::new(particle_type(0, None));
+
+    let mut sim = ParticleSim::new_seeded(16, 16, stone, 1);
+    for (x, y) in [(0, 0), (5, 9), (15, 15)] {
+        assert_eq!(sim.particle_at(x, y).color_noise, texture.sample(x as i64, y as i64));
+    }
+
+    sim.paint(8, 8, 8, air);
+    sim.set_particle(3, 4, stone);
+    sim.paint(12, 12, 2, stone);
+    assert_eq!(sim.particle_at(3, 4).color_noise, texture.sample(3, 4));
+    assert_eq!(sim.particle_at(12, 13).color_noise, texture.sample(12, 13));
+    assert_eq!(sim.particle_at(8, 8).color_noise, 128);
+
+    sim.resize(20, 16, Anchor::TopLeft, stone).unwrap();
+    assert_eq!(sim.particle_at(18, 2).color_noise, texture.sample(18, 2));
+    assert_eq!(sim.particle_at(8, 8).color_noise, 128);
+}
+
+#[test]
+fn textures_survive_saving() {
+    for (i, pattern) in all_patterns().into_iter().enumerate() {
+        let textured = Particle::new(particle_type(i as u32 + 1, Some(Texture::new(pattern, 30, i as u64))));
+        let mut sim = ParticleSim::new_seeded(8, 8, textured, 5);
+        sim.set_boundary(Edge::Top, Boundary::Open(textured));
+
+        let mut bytes = Vec::new();
+        sim.write_to(&mut bytes).unwrap();
+        let loaded = ParticleSim::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded.particle_at(3, 3), sim.particle_at(3, 3));
+        assert!(matches!(loaded.boundary(Edge::Top), Boundary::Open(particle) if particle == textured));
+    }
+}
+
+#[test]
+fn validate_rejects_broken_textures() {
+    let broken = [
+        Pattern::Metal { size: 0 },
+        Pattern::ValueNoise { scale: 0.0 },
+        Pattern::Perlin { scale: f32::NAN, octaves: 3 },
+        Pattern::Wood { ring_spacing: -1.0, grain: 1.0 },
+        Pattern::Brick { width: 0, height: 4, mortar: 1 },
+        Pattern::Crystal { size: f32::INFINITY },
+        Pattern::Speckle { density: 1.5 },
+    ];
+    for pattern in broken {
+        assert!(particle_type(1, Some(Texture::new(pattern, 10, 0))).validate().is_err(), "{:?} got through", pattern);
+    }
+    for pattern in all_patterns() {
+        assert!(particle_type(1, Some(Texture::new(pattern, 10, 0))).validate().is_ok());
+    }
+}
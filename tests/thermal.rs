@@ -0,0 +1,66 @@
+use simple_particle_sim::error::SimError;
+use simple_particle_sim::particle_sim::{Particle, ParticleSim, ParticleType};
+
+fn water() -> ParticleType {
+    ParticleType {
+        solid: true,
+        liquid_density: 1.0,
+        gas_density: 0.0006,
+        melting_temperature: 273,
+        boiling_temperature: 373,
+        heat_capacity: 4186,
+        ..ParticleType::new(0)
+    }
+}
+
+fn energy_at(temperature: u32) -> u64 {
+    Particle::new(water()).set_temperature(temperature).energy
+}
+
+#[test]
+fn melting_ice_takes_more_than_heating_steam() {
+    let melting = energy_at(283) - energy_at(263);
+    let steam = energy_at(420) - energy_at(400);
+    assert_eq!(Particle::new(water()).set_temperature(263).get_state(), 0);
+    assert_eq!(Particle::new(water()).set_temperature(400).get_state(), 3);
+    assert!(melting > 1000 * steam, "ice took {} and steam {}", melting, steam);
+}
+
+#[test]
+fn ice_and_water_warm_at_the_same_rate() {
+    let ice = energy_at(270) - energy_at(260);
+    let water = energy_at(290) - energy_at(280);
+    assert!(ice.abs_diff(water) <= 2, "ice took {} and water {}", ice, water);
+}
+
+#[test]
+fn plasma_temperatures_fit_and_the_top_saturates() {
+    let plasma = ParticleType { solid: false, ..water() };
+    for temperature in [1_000_000, 50_000_000, 1_000_000_000] {
+        assert_eq!(Particle::new(plasma).set_temperature(temperature).get_temperature(), temperature);
+    }
+    let heavy = ParticleType { heat_capacity: u32::MAX, liquid_density: 20.0, ..plasma };
+    assert_eq!(Particle::new(heavy).set_temperature(u32::MAX).energy, u64::MAX);
+    assert!(matches!(Particle::new(heavy).try_set_temperature(u32::MAX), Err(SimError::EnergyOverflow { .. })));
+}
+
+#[test]
+fn temperatures_finer_than_a_kelvin_survive() {
+    for temperature in [0.25, 250.5, 300.125, 1000.75] {
+        let back = Particle::new(water()).set_temperature_f64(temperature).get_temperature_f64();
+        assert!((back - temperature).abs() < 0.001, "{} came back as {}", temperature, back);
+    }
+}
+
+#[test]
+fn heat_flow_keeps_the_total_energy() {
+    let mut sim = ParticleSim::new_seeded(48, 48, Particle::new(water()).set_temperature(250), 9);
+    sim.paint(20, 20, 8, Particle::new(water()).set_temperature(2_000_000));
+    sim.paint(36, 36, 5, Particle::new(ParticleType { heat_capacity: 900, ..water() }).set_temperature(900));
+    let before = sim.total_energy();
+    for t in 0..50 {
+        sim.simulate_heat(t);
+    }
+    assert!(sim.stats().max_temperature < 2_000_000.0);
+    assert_eq!(sim.total_energy(), before);
+}
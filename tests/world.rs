@@ -0,0 +1,106 @@
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use simple_particle_sim::error::SimError;
+use simple_particle_sim::particle_sim::{Particle, ParticleType};
+use simple_particle_sim::region::Anchor;
+use simple_particle_sim::world::{World, PAGE_SIZE};
+
+fn air() -> ParticleType {
+    ParticleType { liquid_density: 0.001, gas_density: 0.0012, melting_temperature: 10, boiling_temperature: 20, ..ParticleType::new(0) }
+}
+
+fn stone() -> ParticleType {
+    ParticleType {
+        solid: true,
+        liquid_density: 2.5,
+        gas_density: 2.5,
+        melting_temperature: 1500,
+        boiling_temperature: 3000,
+        ..ParticleType::new(1)
+    }
+}
+
+// ground below y = 0, air above
+fn ground(_: i64, y: i64) -> Particle {
+    Particle::new(if y >= 0 { stone() } else { air() }).set_temperature(300)
+}
+
+// an empty directory of its own for every test
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("particle-sim-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn pages_go_to_disk_and_come_back() {
+    let dir = dir("paging");
+    let calls = Rc::new(Cell::new(0));
+    let counted = calls.clone();
+    let mut world = World::new(&dir, 2, 2, 7, move |x, y| {
+        counted.set(counted.get() + 1);
+        ground(x, y)
+    })
+    .unwrap();
+    assert_eq!(world.particle_at(-5, -5).unwrap().particle_type.id, air().id);
+    assert_eq!(world.particle_at(5, 5).unwrap().particle_type.id, stone().id);
+
+    let marker = Particle::new(ParticleType { id: 2, ..stone() }).set_temperature(300);
+    assert!(world.set_particle(-20, 10, marker));
+
+    // far enough that nothing of the old window is kept, the new pages have never been seen so they're generated
+    let before = calls.get();
+    world.set_focus(10 * PAGE_SIZE as i64, 0).unwrap();
+    assert_eq!(calls.get() - before, 4 * PAGE_SIZE * PAGE_SIZE);
+    assert!(world.particle_at(-20, 10).is_none());
+    assert_eq!(world.particle_at(10 * PAGE_SIZE as i64, 5).unwrap().particle_type.id, stone().id);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+
+    // coming back reads the old pages off the disk instead of generating them again
+    let before = calls.get();
+    world.set_focus(0, 0).unwrap();
+    assert_eq!(calls.get(), before);
+    assert_eq!(world.particle_at(-20, 10).unwrap().particle_type.id, marker.particle_type.id);
+
+    // and a new world in the same directory picks up where this one left off
+    world.save().unwrap();
+    drop(world);
+    let world = World::new(&dir, 2, 2, 7, ground).unwrap();
+    assert_eq!(world.particle_at(-20, 10).unwrap().particle_type.id, marker.particle_type.id);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_window_edge_is_a_wall() {
+    let dir = dir("edge");
+    let mut world = World::new(&dir, 2, 2, 7, ground).unwrap();
+    let (ox, oy) = world.origin();
+    // one bar sticking out of the window's left edge, one hanging in the air in the middle
+    for x in 0..5 {
+        world.set_particle(ox + x, oy + 20, Particle::new(stone()).set_temperature(300));
+        world.set_particle(x - 2, oy + 20, Particle::new(stone()).set_temperature(300));
+    }
+    world.sim_mut().simulate_structure(0);
+    // the one in the middle falls as a rigid body, the other counts as held up by the edge
+    assert_eq!(world.particle_at(ox, oy + 20).unwrap().get_body(), 0);
+    assert_ne!(world.particle_at(0, oy + 20).unwrap().get_body(), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_resized_window_is_an_error_rather_than_a_panic() {
+    let dir = dir("resized");
+    let mut world = World::new(&dir, 2, 2, 7, ground).unwrap();
+    let size = 2 * PAGE_SIZE;
+    world.sim_mut().resize(size / 2, size, Anchor::TopLeft, Particle::new(air())).unwrap();
+    assert!(matches!(world.set_focus(10 * PAGE_SIZE as i64, 0), Err(SimError::InvalidSize { .. })));
+    assert!(matches!(world.save(), Err(SimError::InvalidSize { .. })));
+
+    // back at the right size it's all fine again
+    world.sim_mut().resize(size, size, Anchor::TopLeft, Particle::new(air())).unwrap();
+    world.set_focus(10 * PAGE_SIZE as i64, 0).unwrap();
+    world.save().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}